@@ -0,0 +1,89 @@
+//! Benchmarks for matching the cookies of large jars against request URLs.
+//!
+//! Each jar holds cookies for many sites, spread over the domain of each site, a subdomain and a
+//! few paths, so a lookup has to walk a wide tree of domains and paths.
+
+#[macro_use]
+extern crate criterion;
+extern crate cookie_jar;
+extern crate url;
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion};
+use cookie_jar::{ClockFn, Cookie, Jar};
+use url::Url;
+
+/// The number of cookies in each jar benchmarked.
+const SIZES: &[usize] = &[10_000, 100_000];
+
+/// The cookies set for each site.
+const SITE_COOKIES: &[(&str, &str)] = &[
+    ("https://{}/", "session={}; Path=/; Secure; HttpOnly"),
+    ("https://{}/", "shared={}; Domain={}; Path=/"),
+    ("https://www.{}/", "lang=en-{}; Path=/"),
+    ("https://www.{}/docs/", "theme={}"),
+    ("https://www.{}/docs/", "page={}; Path=/docs/guide/intro"),
+    ("https://api.{}/v1/", "token={}; Path=/v1"),
+    ("https://{}/account/", "prefs={}; Path=/account/settings"),
+    ("https://{}/", "tracking={}; Path=/; SameSite=None; Secure"),
+    ("https://static.{}/", "cdn={}; Path=/assets"),
+    ("https://www.{}/", "consent={}; Domain={}; Path=/"),
+];
+
+/// The requests made to each site.
+const REQUESTS: &[(&str, &str)] = &[
+    ("hit", "https://www.{}/docs/guide/intro/index.html"),
+    ("root", "https://{}/"),
+    ("miss", "https://www.unknown-{}/docs/guide/intro/index.html"),
+];
+
+/// Fill a template with the name of a site.
+fn fill(template: &str, site: &str) -> String {
+    template.replace("{}", site)
+}
+
+/// Create a jar holding a number of cookies.
+fn jar(size: usize) -> Jar<ClockFn> {
+    let mut jar = Jar::default();
+    for site in 0..size / SITE_COOKIES.len() {
+        let site = format!("site{}.example", site);
+        for &(origin, set_cookie) in SITE_COOKIES {
+            let origin: Url = fill(origin, &site).parse().unwrap();
+            let cookie = Cookie::parse(&fill(set_cookie, &site), &origin).unwrap();
+            jar.add_cookie(cookie, None);
+        }
+    }
+    jar
+}
+
+fn url_matches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("url_matches");
+    for &size in SIZES {
+        let jar = jar(size);
+        let site = format!("site{}.example", size / SITE_COOKIES.len() / 2);
+        for &(name, url) in REQUESTS {
+            let url: Url = fill(url, &site).parse().unwrap();
+            group.bench_with_input(BenchmarkId::new(name, size), &url, |b, url| {
+                b.iter(|| jar.url_matches(black_box(url), None).count())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn cookie_header(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cookie_header");
+    for &size in SIZES {
+        let jar = jar(size);
+        let site = format!("site{}.example", size / SITE_COOKIES.len() / 2);
+        let url: Url = fill(REQUESTS[0].1, &site).parse().unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(size), &url, |b, url| {
+            b.iter(|| jar.cookie_header(black_box(url), None))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, url_matches, cookie_header);
+criterion_main!(benches);
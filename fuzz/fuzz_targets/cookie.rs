@@ -0,0 +1,23 @@
+//! Parse a `Set-Cookie` header received from a fixed origin into a cookie for the jar.
+
+#![no_main]
+
+use cookie_jar::Cookie;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let source = match std::str::from_utf8(data) {
+        Ok(source) => source,
+        Err(_) => return,
+    };
+
+    let origin = "https://www.example.com/path/page.html".parse().unwrap();
+    if let Ok(cookie) = Cookie::parse(source, &origin) {
+        let _ = cookie.pair().as_tuple();
+        let _ = (cookie.host(), cookie.domain(), cookie.path());
+        let _ = cookie.expired();
+        for extension in cookie.extensions() {
+            let _ = (extension.name(), extension.value());
+        }
+    }
+});
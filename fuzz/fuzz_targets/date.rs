@@ -0,0 +1,10 @@
+//! Parse the value of an `Expires` attribute.
+
+#![no_main]
+
+use cookie_jar::fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = fuzz::parse_date(data);
+});
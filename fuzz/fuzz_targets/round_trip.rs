@@ -0,0 +1,27 @@
+//! Rendering a parsed `Set-Cookie` header and parsing it again gives the same cookie.
+
+#![no_main]
+
+use cookie_jar::SetCookie;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let source = match std::str::from_utf8(data) {
+        Ok(source) => source,
+        Err(_) => return,
+    };
+
+    let cookie = match SetCookie::parse(source) {
+        Ok(cookie) => cookie,
+        Err(_) => return,
+    };
+
+    let rendered = cookie.to_string();
+    let reparsed = match SetCookie::parse(&rendered) {
+        Ok(reparsed) => reparsed,
+        Err(error) => panic!("{:?} rendered as {:?} failed to parse: {}", source, rendered, error),
+    };
+
+    assert_eq!(cookie, reparsed, "{:?} rendered as {:?}", source, rendered);
+    assert_eq!(rendered, reparsed.to_string());
+});
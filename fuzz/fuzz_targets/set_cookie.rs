@@ -0,0 +1,22 @@
+//! Parse a `Set-Cookie` header as sent by a server.
+
+#![no_main]
+
+use cookie_jar::SetCookie;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let source = match std::str::from_utf8(data) {
+        Ok(source) => source,
+        Err(_) => return,
+    };
+
+    if let Ok(cookie) = SetCookie::parse(source) {
+        let _ = cookie.pair().as_tuple();
+        let _ = (cookie.domain(), cookie.path());
+        for extension in cookie.extensions() {
+            let _ = (extension.name(), extension.value());
+        }
+        let _ = cookie.to_string();
+    }
+});
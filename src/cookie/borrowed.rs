@@ -0,0 +1,243 @@
+//! A view of a `Set-Cookie` header that borrows from the header instead of copying it.
+
+use std::str::from_utf8;
+use std::time::{Duration, SystemTime};
+
+use url::Url;
+
+use super::parse::{process_cookie_ref, Argument, ArgumentIter, PairRef};
+use super::{Builder, Cookie, SameSite, SetCookie};
+use datetime::from_tm;
+use error::*;
+
+/// A `Set-Cookie` directive parsed into slices of the header it was read from.
+///
+/// Parsing does not allocate, which makes this suited to inspecting cookies that may never be
+/// stored. It can be converted into a [`SetCookie`](struct.SetCookie.html) or a
+/// [`Cookie`](struct.Cookie.html) when the cookie is kept.
+///
+/// The `Domain` attribute is given as it appears in the header. It is only checked and normalised
+/// when the cookie is converted, so conversion can fail for a view that parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetCookieRef<'a> {
+    /// The `name=value` pair of the cookie.
+    pair: PairRef<'a>,
+
+    /// The attributes following the pair, starting with their delimiter.
+    arguments: &'a str,
+
+    /// Domain restriction of the cookie, as given.
+    domain: Option<&'a str>,
+
+    /// Path restriction of the cookie.
+    path: Option<&'a str>,
+
+    /// The expiration time of the cookie in UTC.
+    expiry: Option<SystemTime>,
+
+    /// The lifetime of the cookie relative to when it was received.
+    max_age: Option<Duration>,
+
+    /// The cookie can only be sent over a TLS connection.
+    secure: bool,
+
+    /// The cookie can only be sent via a HTTP (or HTTPS) connection.
+    http_only: bool,
+
+    /// Restriction on sending the cookie with cross-site requests.
+    same_site: Option<SameSite>,
+
+    /// The cookie is stored separately for each top-level site.
+    partitioned: bool,
+}
+
+impl<'a> SetCookieRef<'a> {
+    /// Parse a given cookie without copying it.
+    ///
+    /// When an attribute is given more than once the last one is used.
+    pub fn parse(cookie: &'a str) -> Result<SetCookieRef<'a>> {
+        let (pair, args) = process_cookie_ref(cookie)?;
+        let mut set_cookie = SetCookieRef {
+            pair,
+            arguments: &cookie[pair.as_str().len()..],
+            domain: None,
+            path: None,
+            expiry: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+            partitioned: false,
+        };
+
+        for arg in args {
+            match arg? {
+                Argument::Expires(time) => set_cookie.expiry = Some(from_tm(&time)),
+                Argument::MaxAge(duration) => set_cookie.max_age = Some(duration),
+                Argument::Domain(domain) => set_cookie.domain = Some(domain),
+                Argument::Path(path) => set_cookie.path = Some(path),
+                Argument::Secure => set_cookie.secure = true,
+                Argument::HttpOnly => set_cookie.http_only = true,
+                Argument::SameSite(same_site) => set_cookie.same_site = Some(same_site),
+                Argument::Partitioned => set_cookie.partitioned = true,
+                Argument::Extension(_) => {}
+            }
+        }
+
+        ensure!(
+            !set_cookie.partitioned || set_cookie.secure,
+            ErrorKind::InsecurePartitioned
+        );
+        Ok(set_cookie)
+    }
+
+    /// Get the (name, value) pair of the cookie.
+    pub fn pair(&self) -> PairRef<'a> {
+        self.pair
+    }
+
+    /// Get the name of the cookie.
+    pub fn name(&self) -> &'a str {
+        self.pair.name()
+    }
+
+    /// Get the value of the cookie.
+    pub fn value(&self) -> &'a str {
+        self.pair.value()
+    }
+
+    /// Get the domain the cookie applies to, as given in the header.
+    pub fn domain(&self) -> Option<&'a str> {
+        self.domain
+    }
+
+    /// Get the path the cookie applies to.
+    pub fn path(&self) -> Option<&'a str> {
+        self.path
+    }
+
+    /// Get the expiry time of the cookie, if one was given.
+    pub fn expiry(&self) -> Option<SystemTime> {
+        self.expiry
+    }
+
+    /// Get the maximum age of the cookie, if one was given.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    /// Check if the cookie requires a secure connection.
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    /// Check if the cookie should only be sent over http requests.
+    pub fn http_only(&self) -> bool {
+        self.http_only
+    }
+
+    /// Get the cross-site enforcement mode of the cookie, if one was given.
+    pub fn same_site(&self) -> Option<SameSite> {
+        self.same_site
+    }
+
+    /// Check if the cookie is stored separately for each top-level site.
+    pub fn partitioned(&self) -> bool {
+        self.partitioned
+    }
+
+    /// Get the extension attributes of the cookie in the order they were given.
+    ///
+    /// Each attribute is either a bare `name` or a `name=value` pair.
+    pub fn extensions(&self) -> impl Iterator<Item = &'a str> {
+        ArgumentIter::new(self.arguments.as_bytes()).filter_map(|arg| match arg {
+            Ok(Argument::Extension(extension)) => from_utf8(extension).ok(),
+            _ => None,
+        })
+    }
+
+    /// Copy the cookie into an owned SetCookie.
+    pub fn to_set_cookie(&self) -> Result<SetCookie> {
+        self.build(Builder::new())?.build_set_cookie()
+    }
+
+    /// Copy the cookie into an owned Cookie received from a given origin.
+    pub fn to_cookie(&self, origin: &Url) -> Result<Cookie> {
+        self.build(Builder::new().origin(origin))?.build_cookie()
+    }
+
+    /// Apply the cookie to a builder.
+    fn build(&self, builder: Builder) -> Result<Builder> {
+        let args = ArgumentIter::new(self.arguments.as_bytes());
+        builder.pair(self.pair.to_pair()).arguments(args)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cookie::Extension;
+
+    #[test]
+    fn borrowed_attributes() {
+        let header = "SID=\"31d4d96e407aad42\"; Path=/docs; Domain=Example.com; Secure; \
+                      HttpOnly; Max-Age=60; Expires=Wed, 09 Jun 2021 10:18:14 GMT; \
+                      SameSite=Lax; Priority=High; Flagged";
+        let cookie = SetCookieRef::parse(header).expect("Could not parse cookie");
+
+        assert_eq!(cookie.pair().as_str(), "SID=\"31d4d96e407aad42\"");
+        assert_eq!((cookie.name(), cookie.value()), ("SID", "31d4d96e407aad42"));
+        assert_eq!(cookie.domain(), Some("Example.com"));
+        assert_eq!(cookie.path(), Some("/docs"));
+        assert_eq!(cookie.max_age(), Some(Duration::from_secs(60)));
+        assert!(cookie.expiry().is_some());
+        assert!(cookie.secure() && cookie.http_only() && !cookie.partitioned());
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(cookie.extensions().collect::<Vec<_>>(), ["Priority=High", "Flagged"]);
+
+        // The views borrow from the header.
+        let range = header.as_ptr() as usize..header.as_ptr() as usize + header.len();
+        assert!(range.contains(&(cookie.value().as_ptr() as usize)));
+        assert!(range.contains(&(cookie.path().unwrap().as_ptr() as usize)));
+    }
+
+    #[test]
+    fn convert_to_owned() {
+        let origin: Url = "https://www.example.com/docs/index.html".parse().unwrap();
+        let headers = [
+            "SID=31d4d96e407aad42",
+            "SID=31d4d96e407aad42; Path=/; Domain=example.com",
+            "lang=en-US; Expires=Wed, 09 Jun 2021 10:18:14 GMT; Max-Age=0; Path=/a; Path=/b",
+            "empty=; SameSite=Strict; SameSite=Sometimes; x-vendor=one; X-Vendor=two",
+            "__Host-id=1; Path=/; Secure; Partitioned; HttpOnly",
+        ];
+
+        for header in headers.iter() {
+            let cookie = SetCookieRef::parse(header).expect("Could not parse cookie");
+            assert_eq!(cookie.to_set_cookie().unwrap(), SetCookie::parse(header).unwrap());
+            assert_eq!(
+                cookie.to_cookie(&origin).unwrap().pair(),
+                Cookie::parse(header, &origin).unwrap().pair()
+            );
+        }
+
+        let owned = SetCookieRef::parse(headers[3]).unwrap().to_set_cookie().unwrap();
+        let names: Vec<_> = owned.extensions().iter().map(Extension::name).collect();
+        assert_eq!(names, ["SameSite", "x-vendor", "X-Vendor"]);
+    }
+
+    #[test]
+    fn invalid_cookies() {
+        for header in ["bad name=1", "SID=1;Path=/", "SID=1; Max-Age=soon", "id=1; Partitioned"]
+            .iter()
+        {
+            assert!(SetCookieRef::parse(header).is_err(), "{:?} should not parse", header);
+            assert!(SetCookie::parse(header).is_err(), "{:?} should not parse", header);
+        }
+
+        // Domains are only checked when the cookie is copied.
+        let cookie = SetCookieRef::parse("SID=1; Domain=%3B.example").unwrap();
+        assert_eq!(cookie.domain(), Some("%3B.example"));
+        assert!(cookie.to_set_cookie().is_err());
+    }
+}
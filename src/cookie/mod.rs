@@ -1,13 +1,17 @@
 //! Representation of a cookie within the value store.
 
-mod parse;
+mod borrowed;
+pub(crate) mod parse;
 
+use std::fmt;
 use std::ops::Deref;
+use std::time::{Duration, SystemTime};
 
-use self::parse::{process_cookie, Argument};
-pub use self::parse::Pair;
+pub use self::borrowed::SetCookieRef;
+use self::parse::{process_cookie, Argument, ArgumentIter};
+pub use self::parse::{Extension, Pair, PairRef};
+use datetime::{from_tm, to_tm};
 use error::*;
-use time::{now_utc, strftime, Tm};
 use url::{Host, Url};
 
 /// A builder for a cookie.
@@ -97,15 +101,15 @@ impl Builder {
 
     /// Set the host for a cookie to match a given string.
     pub fn host_str(self, host: &str) -> Builder {
-        match Host::parse(host) {
+        match parse_host(host) {
             Ok(host) => self.host(host),
-            Err(error) => self.error(error.into()),
+            Err(error) => self.error(error),
         }
     }
 
     /// Set the domain for a cookie to match a a given domain and all subdomains.
     pub fn domain(self, domain: &str) -> Builder {
-        match Host::parse(domain) {
+        match parse_host(domain) {
             Ok(host) => Builder {
                 host: Some(host),
                 attributes: Attributes {
@@ -116,7 +120,7 @@ impl Builder {
                 ..
                 self
             },
-            Err(error) => self.error(error.into()),
+            Err(error) => self.error(error),
         }
     }
 
@@ -151,10 +155,51 @@ impl Builder {
     }
 
     /// Set the expiry time of a cookie.
-    pub fn expiry(self, time: Tm) -> Builder {
+    pub fn expiry<T: Into<SystemTime>>(self, time: T) -> Builder {
+        Builder {
+            attributes: Attributes {
+                expiry: Expires::AtUtc(time.into()),
+                ..
+                self.attributes
+            },
+            ..
+            self
+        }
+    }
+
+    /// Set the maximum age of a cookie relative to when it is received.
+    ///
+    /// When a cookie is built the maximum age takes precedence over any expiry time.
+    pub fn max_age(self, max_age: Duration) -> Builder {
+        Builder {
+            attributes: Attributes {
+                max_age: Some(max_age),
+                ..
+                self.attributes
+            },
+            ..
+            self
+        }
+    }
+
+    /// Set the time at which the cookie was first stored.
+    pub fn creation_time<T: Into<SystemTime>>(self, time: T) -> Builder {
         Builder {
             attributes: Attributes {
-                expiry: Expires::AtUtc(time),
+                creation_time: Some(time.into()),
+                ..
+                self.attributes
+            },
+            ..
+            self
+        }
+    }
+
+    /// Set the time at which the cookie was last sent.
+    pub fn last_access_time<T: Into<SystemTime>>(self, time: T) -> Builder {
+        Builder {
+            attributes: Attributes {
+                last_access_time: Some(time.into()),
                 ..
                 self.attributes
             },
@@ -189,9 +234,60 @@ impl Builder {
         }
     }
 
+    /// Set whether the cookie is stored separately for each top-level site.
+    ///
+    /// Partitioned cookies must also be secure.
+    pub fn partitioned(self, partitioned: bool) -> Builder {
+        Builder {
+            attributes: Attributes {
+                partitioned,
+                ..
+                self.attributes
+            },
+            ..
+            self
+        }
+    }
+
+    /// Set the cross-site enforcement mode of the cookie.
+    pub fn same_site(self, same_site: SameSite) -> Builder {
+        Builder {
+            attributes: Attributes {
+                same_site: Some(same_site),
+                ..
+                self.attributes
+            },
+            ..
+            self
+        }
+    }
+
+    /// Add an extension attribute to the cookie.
+    pub fn extension(mut self, extension: Extension) -> Builder {
+        self.attributes.extensions.push(extension);
+        self
+    }
+
+    /// Add an extension attribute to the cookie from a string.
+    pub fn extension_str(self, extension: &str) -> Builder {
+        match extension.parse() {
+            Ok(extension) => self.extension(extension),
+            Err(error) => self.error(error.into()),
+        }
+    }
+
+    /// Check that the attributes of the cookie are consistent with each other.
+    fn check_attributes(self) -> Builder {
+        if self.attributes.partitioned && !self.attributes.secure {
+            self.error(ErrorKind::InsecurePartitioned.into())
+        } else {
+            self
+        }
+    }
+
     /// Build the SetCookie.
     pub fn build_set_cookie(self) -> Result<SetCookie> {
-        match self {
+        match self.check_attributes() {
             Builder {
                 host: _,
                 path: _,
@@ -224,7 +320,7 @@ impl Builder {
 
     /// Build the Cookie.
     pub fn build_cookie(self) -> Result<Cookie> {
-        match self {
+        match self.check_attributes() {
             Builder {
                 host: _,
                 path: _,
@@ -251,7 +347,7 @@ impl Builder {
             } => Ok(Cookie {
                 host: host,
                 path: path,
-                attributes: attributes,
+                attributes: attributes.resolve_max_age(SystemTime::now()),
             }),
         }
     }
@@ -259,34 +355,44 @@ impl Builder {
     /// Parse a given cookie into a builder.
     fn parse(self, cookie: &str) -> Result<Builder> {
         let (pair, args) = process_cookie(cookie)?;
-        let mut builder = self.pair(pair);
+        self.pair(pair).arguments(args)
+    }
 
-        // If a Max-Age argument has been seen, Expires should be ignored.
-        let mut use_max_age = false;
+    /// Apply the parsed arguments of a cookie to a builder.
+    fn arguments(self, args: ArgumentIter) -> Result<Builder> {
+        let mut builder = self;
 
+        // Max-Age and Expires are both retained; the precedence of Max-Age is applied when the
+        // cookie is built.
         for arg in args {
-            match (arg?, use_max_age) {
-                (Argument::Expires(time), false) => {
-                    builder = builder.expiry(time);
+            match arg? {
+                Argument::Expires(time) => {
+                    builder = builder.expiry(from_tm(&time));
                 }
-                (Argument::MaxAge(duration), _) => {
-                    builder = builder.expiry(now_utc() + duration);
-                    use_max_age = true;
+                Argument::MaxAge(duration) => {
+                    builder = builder.max_age(duration);
                 }
-                (Argument::Domain(domain), _) => {
+                Argument::Domain(domain) => {
                     builder = builder.domain(domain);
                 }
-                (Argument::Path(path), _) => {
+                Argument::Path(path) => {
                     builder = builder.path(path);
                 }
-                (Argument::Secure, _) => {
+                Argument::Secure => {
                     builder = builder.secure(true);
                 }
-                (Argument::HttpOnly, _) => {
+                Argument::HttpOnly => {
                     builder = builder.http_only(true);
                 }
-                // Ignore all others
-                _ => {}
+                Argument::SameSite(same_site) => {
+                    builder = builder.same_site(same_site);
+                }
+                Argument::Partitioned => {
+                    builder = builder.partitioned(true);
+                }
+                Argument::Extension(extension) => {
+                    builder = builder.extension(Extension::from_bytes(extension)?);
+                }
             }
         }
 
@@ -295,7 +401,7 @@ impl Builder {
 }
 
 /// The SetCookie directive sent from the server.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct SetCookie {
     /// Domain or host restriction of the cookie.
     domain: Option<String>,
@@ -330,11 +436,26 @@ impl SetCookie {
     pub fn path(&self) -> Option<&str> {
         self.path.as_ref().map(String::as_str)
     }
+
+    /// Set the domain the cookie applies to.
+    pub(crate) fn set_domain(&mut self, domain: Option<String>) {
+        self.domain = domain;
+    }
+
+    /// Set the path the cookie applies to.
+    pub(crate) fn set_path(&mut self, path: Option<String>) {
+        self.path = path;
+    }
+
+    /// Set whether the cookie requires a secure connection.
+    pub(crate) fn set_secure(&mut self, secure: bool) {
+        self.attributes.secure = secure;
+    }
 }
 
 /// This is the form that the cookie is represented in within the jar.
 /// It is formed by parsing the provided string into a cookie object.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cookie {
     /// Domain or host restriction of the cookie.
     host: Host,
@@ -383,14 +504,41 @@ impl Cookie {
         self.path.as_str()
     }
 
-    pub(crate) fn explode(self) -> (Host, String, Attributes) {
-        let Cookie { host, path, attributes } = self;
-        (host, path, attributes)
+    /// Recompute the expiry of a cookie with a maximum age relative to the time it was stored.
+    pub(crate) fn set_stored_time(&mut self, now: SystemTime) {
+        if let Some(max_age) = self.attributes.max_age {
+            self.attributes.expiry = expiry_after(now, max_age);
+        }
+    }
+
+    /// Make the cookie expire at the end of the session.
+    pub(crate) fn make_session(&mut self) {
+        self.attributes.expiry = Expires::Never;
+        self.attributes.max_age = None;
+    }
+
+    /// Make a persistent cookie expire no later than a given duration after a time.
+    pub(crate) fn limit_lifetime(&mut self, now: SystemTime, limit: Duration) {
+        let limit = expiry_after(now, limit);
+        if self.attributes.expiry != Expires::Never && self.attributes.expiry > limit {
+            self.attributes.expiry = limit;
+            self.attributes.max_age = None;
+        }
+    }
+
+    /// Set the time at which the cookie was first stored.
+    pub(crate) fn set_creation_time(&mut self, time: SystemTime) {
+        self.attributes.creation_time = Some(time);
+    }
+
+    /// Set the time at which the cookie was last sent.
+    pub(crate) fn set_last_access_time(&mut self, time: SystemTime) {
+        self.attributes.last_access_time = Some(time);
     }
 }
 
 /// The payload of the cookie including security requirements.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Attributes {
     /// Data stored within the cookie (key = value pair).
     pair: Pair,
@@ -398,6 +546,15 @@ pub struct Attributes {
     /// The expiration time of the cookie in UTC.
     expiry: Expires,
 
+    /// The lifetime of the cookie relative to when it was received.
+    max_age: Option<Duration>,
+
+    /// The time at which the cookie was first stored.
+    creation_time: Option<SystemTime>,
+
+    /// The time at which the cookie was last sent.
+    last_access_time: Option<SystemTime>,
+
     /// If the cookie also applies to subdomains.
     host_only: bool,
 
@@ -406,6 +563,15 @@ pub struct Attributes {
 
     /// The cookie can only be sent via a HTTP (or HTTPS) connection.
     http_only: bool,
+
+    /// Restriction on sending the cookie with cross-site requests.
+    same_site: Option<SameSite>,
+
+    /// The cookie is stored separately for each top-level site.
+    partitioned: bool,
+
+    /// Attributes that are not otherwise understood, in the order they were given.
+    extensions: Vec<Extension>,
 }
 
 impl Default for Attributes {
@@ -413,9 +579,15 @@ impl Default for Attributes {
         Attributes {
             pair: Default::default(),
             expiry: Expires::Never,
+            max_age: None,
+            creation_time: None,
+            last_access_time: None,
             host_only: true,
             secure: false,
             http_only: false,
+            same_site: None,
+            partitioned: false,
+            extensions: Vec::new(),
         }
     }
 }
@@ -444,11 +616,11 @@ impl Attributes {
 
     /// Check if the cookie has expired.
     pub fn expired(&self) -> bool {
-        self.expired_since(now_utc())
+        self.expired_since(SystemTime::now())
     }
 
     /// Check if the cookie was expired after a given time.
-    pub fn expired_since(&self, time: Tm) -> bool {
+    pub fn expired_since(&self, time: SystemTime) -> bool {
         match self.expiry {
             Expires::Never => false,
             Expires::AtUtc(expiry) => time >= expiry,
@@ -459,6 +631,58 @@ impl Attributes {
     pub fn expiry(&self) -> &Expires {
         &self.expiry
     }
+
+    /// Get the maximum age of the cookie, if one was given.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    /// Get the time at which the cookie was first stored, if known.
+    pub fn creation_time(&self) -> Option<SystemTime> {
+        self.creation_time
+    }
+
+    /// Get the time at which the cookie was last sent, if known.
+    pub fn last_access_time(&self) -> Option<SystemTime> {
+        self.last_access_time
+    }
+
+    /// Get the cross-site enforcement mode of the cookie, if one was given.
+    pub fn same_site(&self) -> Option<SameSite> {
+        self.same_site
+    }
+
+    /// Check if the cookie is stored separately for each top-level site.
+    pub fn partitioned(&self) -> bool {
+        self.partitioned
+    }
+
+    /// Get all of the extension attributes of the cookie.
+    pub fn extensions(&self) -> &[Extension] {
+        &self.extensions
+    }
+
+    /// Get the last extension attribute with a given name.
+    ///
+    /// Attribute names are matched case insensitively.
+    pub fn extension(&self, name: &str) -> Option<&Extension> {
+        self.extensions
+            .iter()
+            .rev()
+            .find(|extension| extension.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Convert the maximum age of the cookie into an expiry relative to a given time.
+    fn resolve_max_age(self, now: SystemTime) -> Attributes {
+        match self.max_age {
+            Some(max_age) => Attributes {
+                expiry: expiry_after(now, max_age),
+                ..
+                self
+            },
+            None => self,
+        }
+    }
 }
 
 impl Deref for Attributes {
@@ -469,45 +693,57 @@ impl Deref for Attributes {
     }
 }
 
-impl ::std::string::ToString for SetCookie {
-    fn to_string(&self) -> String {
-        let mut cookie = self.pair.as_str().to_owned();
+/// Render the cookie using the `set-cookie-string` grammar of
+/// [RFC6265 section 4.1.1](https://tools.ietf.org/html/rfc6265#section-4.1.1).
+impl fmt::Display for SetCookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.pair.as_str())?;
 
         if let Some(ref path) = self.path {
-            cookie = format!("{}; Path={}", cookie, path);
+            write!(f, "; Path={}", path)?;
         }
 
         if let Some(ref domain) = self.domain {
-            cookie = format!("{}; Domain={}", cookie, domain);
+            write!(f, "; Domain={}", domain)?;
         }
 
         if self.secure() {
-            cookie = format!("{}; Secure", cookie);
+            f.write_str("; Secure")?;
         }
 
         if self.http_only() {
-            cookie = format!("{}; HttpOnly", cookie);
+            f.write_str("; HttpOnly")?;
+        }
+
+        if let Expires::AtUtc(time) = *self.expiry() {
+            write!(f, "; Expires={}", to_tm(time).rfc822())?;
         }
 
-        if let Expires::AtUtc(ref time) = self.expiry() {
-            cookie = format!(
-                "{}; Expires={}",
-                cookie,
-                strftime("%a, %d %b %Y %T %z", time).unwrap()
-            );
+        if let Some(max_age) = self.max_age() {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
         }
 
-        // Add Max-Age expiry.
+        if let Some(same_site) = self.same_site() {
+            write!(f, "; SameSite={}", same_site)?;
+        }
 
-        cookie
+        if self.partitioned() {
+            f.write_str("; Partitioned")?;
+        }
+
+        for extension in self.extensions() {
+            write!(f, "; {}", extension.as_str())?;
+        }
+
+        Ok(())
     }
 }
 
 /// Expiry time of a cookie.
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum Expires {
-    /// The cookie expires at a specified time from UTC.
-    AtUtc(Tm),
+    /// The cookie expires at a specified time.
+    AtUtc(SystemTime),
     /// The cookie never expires.
     Never,
 }
@@ -518,6 +754,64 @@ impl Default for Expires {
     }
 }
 
+/// Parse a host, rejecting domains that cannot be written back into a cookie header.
+pub(crate) fn parse_host(host: &str) -> Result<Host> {
+    let host = Host::parse(host)?;
+    if let Host::Domain(ref domain) = host {
+        ensure!(
+            domain.bytes().all(|byte| !byte.is_ascii_control() && byte != b';'),
+            ErrorKind::HostInvalid
+        );
+    }
+
+    Ok(host)
+}
+
+/// Get the expiry a duration after a time.
+///
+/// Durations that cannot be represented never expire.
+fn expiry_after(time: SystemTime, duration: Duration) -> Expires {
+    time.checked_add(duration)
+        .map(Expires::AtUtc)
+        .unwrap_or(Expires::Never)
+}
+
+/// Restriction on sending a cookie with cross-site requests.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SameSite {
+    /// The cookie is only sent with same-site requests.
+    Strict,
+    /// The cookie is also sent with top-level cross-site navigations.
+    Lax,
+    /// The cookie is sent with all requests.
+    None,
+}
+
+impl SameSite {
+    /// Decode the value of a SameSite attribute case insensitively.
+    fn from_bytes(value: &[u8]) -> Option<SameSite> {
+        if value.eq_ignore_ascii_case(b"Strict") {
+            Some(SameSite::Strict)
+        } else if value.eq_ignore_ascii_case(b"Lax") {
+            Some(SameSite::Lax)
+        } else if value.eq_ignore_ascii_case(b"None") {
+            Some(SameSite::None)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        })
+    }
+}
+
 /// Get the directory of the path of a Url.
 pub(crate) fn url_dir_path(url: &Url) -> &str {
     let path = url.path();
@@ -532,6 +826,8 @@ pub(crate) fn url_dir_path(url: &Url) -> &str {
 #[cfg(test)]
 mod test {
     use super::*;
+    use proptest::prelude::*;
+    use datetime::from_unix_seconds;
 
     #[test]
     /// Examples from [RFC6265](https://tools.ietf.org/html/rfc6265).
@@ -624,6 +920,18 @@ mod test {
                     .unwrap(),
                 "lang=en-US; Path=/; Domain=example.com",
             ),
+            (
+                Builder::new()
+                    .path("/")
+                    .pair_str("lang=en-US")
+                    .expiry(from_unix_seconds(1_262_304_000))
+                    .max_age(Duration::from_secs(3600))
+                    .same_site(SameSite::Lax)
+                    .build_set_cookie()
+                    .unwrap(),
+                "lang=en-US; Path=/; Expires=Fri, 01 Jan 2010 00:00:00 GMT; Max-Age=3600; \
+                 SameSite=Lax",
+            ),
         ];
 
         for &(ref cookie, ref expected) in examples.iter() {
@@ -631,9 +939,123 @@ mod test {
         }
     }
 
-    // TODO: Test for use of last attribute of given name to determine setting on cookie.
+    #[test]
+    fn max_age_precedence() {
+        let origin = "http://www.example.com/".parse().unwrap();
+        let examples = [
+            "SID=31d4d96e407aad42; Expires=Wed, 09 Jun 2021 10:18:14 GMT; Max-Age=3600",
+            "SID=31d4d96e407aad42; Max-Age=3600; Expires=Wed, 09 Jun 2021 10:18:14 GMT",
+        ];
+
+        for cookie_str in examples.iter() {
+            let before = SystemTime::now();
+            let cookie = Cookie::parse(cookie_str, &origin).expect("Could not parse cookie");
+            assert!(!cookie.expired_since(before + Duration::from_secs(3599)));
+            assert!(cookie.expired_since(SystemTime::now() + Duration::from_secs(3600)));
+        }
+    }
+
+    #[test]
+    fn percent_encoded_control_domain() {
+        // Found by the round trip fuzzing target; the decoded domain could not be rendered.
+        assert!(SetCookie::parse("a=b; Domain=%04.example").is_err());
+        assert!(SetCookie::parse("a=b; Domain=%3B.example").is_err());
+    }
+
+    #[test]
+    fn partitioned_requires_secure() {
+        let origin = "https://widget.example/".parse().unwrap();
 
-    // TODO: Test for ignored unknown attributes.
+        let cookie = Cookie::parse("__Host-id=1; Path=/; Secure; Partitioned", &origin)
+            .expect("Could not parse cookie");
+        assert!(cookie.partitioned());
 
-    // TODO: Test for precedence of Max-Age over Expires attributes.
+        let insecure = Cookie::parse("__Host-id=1; Path=/; Partitioned", &origin);
+        match insecure {
+            Err(Error(ErrorKind::InsecurePartitioned, _)) => {}
+            other => panic!("Expected an insecure partitioned error, got {:?}", other),
+        }
+    }
+
+    prop_compose! {
+        fn arb_set_cookie()(
+            pair in "[A-Za-z0-9_]{1,16}=[A-Za-z0-9_.-]{1,16}",
+            path in prop::option::of("/([a-z0-9]{1,8}/){0,3}"),
+            domain in prop::option::of("([a-z0-9]{1,8}\\.){1,3}[a-z]{2,6}"),
+            secure in any::<bool>(),
+            http_only in any::<bool>(),
+            partitioned in any::<bool>(),
+            expiry in prop::option::of(0i64..4_102_444_800),
+            max_age in prop::option::of(0u64..1_000_000_000),
+            same_site in prop::option::of(prop_oneof![
+                Just(SameSite::Strict),
+                Just(SameSite::Lax),
+                Just(SameSite::None),
+            ]),
+            extensions in prop::collection::vec("X[a-z]{1,8}(=[A-Za-z0-9]{0,8})?", 0..3),
+        ) -> SetCookie {
+            let mut builder = Builder::new()
+                .pair_str(&pair)
+                .secure(secure)
+                .http_only(http_only)
+                .partitioned(partitioned && secure);
+
+            if let Some(path) = path {
+                builder = builder.path(&path);
+            }
+            if let Some(domain) = domain {
+                builder = builder.domain(&domain);
+            }
+            if let Some(expiry) = expiry {
+                builder = builder.expiry(from_unix_seconds(expiry));
+            }
+            if let Some(max_age) = max_age {
+                builder = builder.max_age(Duration::from_secs(max_age));
+            }
+            if let Some(same_site) = same_site {
+                builder = builder.same_site(same_site);
+            }
+            for extension in extensions {
+                builder = builder.extension_str(&extension);
+            }
+
+            builder.build_set_cookie().unwrap()
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn render_round_trip(cookie in arb_set_cookie()) {
+            let rendered = cookie.to_string();
+            let parsed = SetCookie::parse(&rendered).expect("Could not parse rendered cookie");
+            prop_assert_eq!(&parsed.to_string(), &rendered);
+            prop_assert_eq!(parsed, cookie);
+        }
+
+        #[test]
+        fn borrowed_round_trip(cookie in arb_set_cookie()) {
+            let rendered = cookie.to_string();
+            let borrowed = SetCookieRef::parse(&rendered).expect("Could not parse rendered cookie");
+            prop_assert_eq!(borrowed.to_set_cookie().unwrap(), cookie);
+        }
+    }
+
+    // TODO: Test for use of last attribute of given name to determine setting on cookie.
+
+    #[test]
+    fn preserve_extensions() {
+        let set_cookie = "SID=31d4d96e407aad42; Path=/; Priority=High; Experimental; \
+                          SameSite=Sometimes; x-vendor=one; X-Vendor=two";
+        let cookie = SetCookie::parse(set_cookie).expect("Could not parse cookie");
+
+        let names: Vec<_> = cookie.extensions().iter().map(Extension::name).collect();
+        assert_eq!(names, ["Priority", "Experimental", "SameSite", "x-vendor", "X-Vendor"]);
+        assert_eq!(cookie.extension("priority").and_then(Extension::value), Some("High"));
+        assert_eq!(cookie.extension("Experimental").map(Extension::value), Some(None));
+        assert_eq!(cookie.extension("x-vendor").and_then(Extension::value), Some("two"));
+        assert_eq!(cookie.extension("Missing"), None);
+        assert_eq!(cookie.same_site(), None);
+
+        assert_eq!(&cookie.to_string(), set_cookie);
+    }
 }
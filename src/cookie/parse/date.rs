@@ -1,5 +1,8 @@
 //! Dates in Cookies have their own parsing rules.
 //!
+//! This implements the algorithm from
+//! [RFC6265 section 5.1.1](https://tools.ietf.org/html/rfc6265#section-5.1.1).
+//!
 //! ```text
 //! cookie-date     = *delimiter date-token-list *delimiter
 //! date-token-list = date-token *( 1*delimiter date-token )
@@ -20,6 +23,7 @@
 //! ```
 
 use super::*;
+use time::at_utc;
 
 /// Is a date delimiter.
 ///
@@ -44,16 +48,6 @@ fn is_non_delimiter(byte: u8) -> bool {
         || byte >= 0x7F
 }
 
-/// Get the next date token.
-fn next_date_token(source: &[u8]) -> Result<&[u8]> {
-    non_zero_length(collect_matching(source, is_non_delimiter))
-}
-
-/// Get the next date delimiter.
-fn next_date_delimiter(source: &[u8]) -> Result<&[u8]> {
-    non_zero_length(collect_matching(source, is_delimiter))
-}
-
 /// Attempt to decode a time token.
 fn decode_time(token: &[u8]) -> Option<(i32, i32, i32)> {
     decode_time_field(token)
@@ -84,7 +78,7 @@ fn decode_next_time_field<T>(decoded: (T, &[u8])) -> Option<(T, (i32, &[u8]))> {
 /// Trailing digits invalidate fields.
 fn invalid_if_trailing_digit<T>(decoded: (T, &[u8])) -> Option<T> {
     let (value, remaining) = decoded;
-    if remaining.len() > 0 && remaining[1].is_ascii_digit() {
+    if remaining.first().is_some_and(u8::is_ascii_digit) {
         None
     } else {
         Some(value)
@@ -146,6 +140,10 @@ fn decode_digits(token: &[u8], min: usize, max: usize) -> Option<(i32, &[u8])> {
         },
     );
 
+    if used < min {
+        return None;
+    }
+
     Some((value, &token[used..]))
 }
 
@@ -153,7 +151,7 @@ fn decode_digits(token: &[u8], min: usize, max: usize) -> Option<(i32, &[u8])> {
 fn decode_year(token: &[u8]) -> Option<i32> {
     let mut decoded = decode_digits(token, 2, 4).and_then(invalid_if_trailing_digit);
 
-    // Uplift 2-digit years.
+    // Years from 0 to 99 are taken to be from 1970 to 2069.
     if let Some(year) = decoded {
         let year = if year >= 0 && year <= 69 {
             year + 2000
@@ -195,9 +193,12 @@ fn verify_date(day: i32, month: i32, year: i32) -> Result<()> {
     ];
 
     // Validate the date.
-    ensure!(day >= 0 && month >= 0 && month < 12, ErrorKind::InvalidDate);
     ensure!(
-        is_leap_year(year) && month == 1 && day <= 29 || day <= month_days[month as usize],
+        (1..=31).contains(&day) && (0..12).contains(&month) && year >= 1601 - 1900,
+        ErrorKind::InvalidDate
+    );
+    ensure!(
+        is_leap_year(year + 1900) && month == 1 && day <= 29 || day <= month_days[month as usize],
         ErrorKind::InvalidDate
     );
 
@@ -207,7 +208,7 @@ fn verify_date(day: i32, month: i32, year: i32) -> Result<()> {
 /// Parse a cookie-date string into an actual datetime.
 pub fn parse(source: &[u8]) -> Result<Tm> {
     let mut date = Date::unset();
-    date.gather(source)?;
+    date.gather(source);
     date.into_time()
 }
 
@@ -231,18 +232,15 @@ impl Date {
     }
 
     /// Gather the raw values from the tokens.
-    fn gather(&mut self, source: &[u8]) -> Result<()> {
+    ///
+    /// Each token sets the first field that has not yet been found and that it can be decoded as.
+    fn gather(&mut self, source: &[u8]) {
         for token in DateIter::new(source) {
-            let token = token?;
-
-            // Try and decode the time from the token.
             Date::try_replace(token, &mut self.time, decode_time)
                 .or_else(|| Date::try_replace(token, &mut self.day, decode_day))
                 .or_else(|| Date::try_replace(token, &mut self.month, decode_month))
                 .or_else(|| Date::try_replace(token, &mut self.year, decode_year));
         }
-
-        Ok(())
     }
 
     /// Convert to a time.
@@ -251,16 +249,16 @@ impl Date {
             (Some((hour, minute, second)), Some(day), Some(month), Some(year)) => {
                 // Validate the time
                 ensure!(
-                    second >= 0 && second < 60
-                        && minute >= 0 && second < 60
-                        && hour >= 0 && hour < 24,
+                    (0..60).contains(&second)
+                        && (0..60).contains(&minute)
+                        && (0..24).contains(&hour),
                     ErrorKind::InvalidDate
                 );
 
                 // Also validate the date
                 verify_date(day, month, year)?;
 
-                Ok(Tm {
+                let time = Tm {
                     tm_sec: second,
                     tm_min: minute,
                     tm_hour: hour,
@@ -272,7 +270,10 @@ impl Date {
                     tm_isdst: 0,
                     tm_utcoff: 0,
                     tm_nsec: 0,
-                })
+                };
+
+                // Round trip through a timestamp to fill in the day of the week and year.
+                Ok(at_utc(time.to_timespec()))
             }
             _ => bail!(ErrorKind::IncompleteDate),
         }
@@ -298,48 +299,33 @@ impl Date {
 }
 
 /// Iterator over a list of date tokens.
+///
+/// Every byte is either a delimiter or part of a token so any leading, trailing or repeated
+/// delimiters are skipped.
 pub struct DateIter<'s> {
     remaining: &'s [u8],
-    first: bool,
 }
 
 impl<'s> DateIter<'s> {
     fn new(source: &'s [u8]) -> DateIter<'s> {
-        DateIter {
-            remaining: source,
-            first: true,
-        }
+        DateIter { remaining: source }
     }
+}
 
-    /// Get the next valid date token
-    fn next_token(&mut self) -> Result<Option<&'s [u8]>> {
-        if self.remaining.len() == 0 {
-            return Ok(None);
-        }
+impl<'s> Iterator for DateIter<'s> {
+    type Item = &'s [u8];
 
-        // Remove leading delimeter
-        if self.first {
-            self.first = false;
-        } else {
-            let delimieter = next_date_delimiter(self.remaining)?;
-            self.remaining = &self.remaining[delimieter.len()..];
-        }
+    fn next(&mut self) -> Option<&'s [u8]> {
+        let delimiter = collect_matching(self.remaining, is_delimiter);
+        self.remaining = &self.remaining[delimiter.len()..];
 
-        // Collect the next token.
-        let token = next_date_token(self.remaining)?;
+        let token = collect_matching(self.remaining, is_non_delimiter);
         self.remaining = &self.remaining[token.len()..];
 
-        Ok(Some(token))
-    }
-}
-
-impl<'s> Iterator for DateIter<'s> {
-    type Item = Result<&'s [u8]>;
-
-    fn next(&mut self) -> Option<Result<&'s [u8]>> {
-        match self.next_token() {
-            Ok(value) => value.map(Ok),
-            Err(err) => Some(Err(err)),
+        if token.is_empty() {
+            None
+        } else {
+            Some(token)
         }
     }
 }
@@ -369,8 +355,47 @@ mod test {
             assert_eq!(
                 parse(parsed.as_bytes())
                     .expect(&format!("Couldn't parse cookie date '{}'", parsed)),
-                strptime(expected, "%Y-%m-%d %H:%M:%S").expect("couldn't parse string date"),
+                at_utc(
+                    strptime(expected, "%Y-%m-%d %H:%M:%S")
+                        .expect("couldn't parse string date")
+                        .to_timespec()
+                ),
             );
         }
     }
+
+    #[test]
+    fn leap_days() {
+        for date in ["Tue, 29 Feb 2000 00:00:00 GMT", "Thu, 29 Feb 2024 00:00:00 GMT"].iter() {
+            let time = parse(date.as_bytes()).expect("Could not parse leap day");
+            assert_eq!((time.tm_mon, time.tm_mday), (1, 29), "{}", date);
+        }
+
+        for date in ["Thu, 29 Feb 1900 00:00:00 GMT", "Wed, 29 Feb 2023 00:00:00 GMT"].iter() {
+            assert!(parse(date.as_bytes()).is_err(), "{} is not a leap day", date);
+        }
+    }
+
+    /// Hand-written cases, not the http-state date corpus.
+    #[test]
+    fn handwritten_dates() {
+        let examples: &[(&str, Option<&str>)] = include!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/handwritten/dates.rs"
+        ));
+
+        for &(test, expected) in examples {
+            let parsed = parse(test.as_bytes()).ok().map(|time| time.rfc822().to_string());
+            assert_eq!(parsed.as_deref(), expected, "Parsing {:?}", test);
+        }
+    }
+
+    #[test]
+    fn malformed_dates() {
+        let tests: &[&[u8]] = &[b"", b"5\x02", b"1a", b"00:00:0"];
+
+        for &source in tests {
+            assert!(parse(source).is_err(), "{:?} should not parse", source);
+        }
+    }
 }
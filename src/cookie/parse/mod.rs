@@ -1,10 +1,13 @@
 //! Parsing for a cookie string.
 
-mod date;
+pub(crate) mod date;
 
 use error::parser::*;
 use std::str::{from_utf8, FromStr};
-use time::{Duration, Tm};
+use std::time::Duration;
+use time::Tm;
+
+use super::SameSite;
 
 /// Byte is a [RFC5234](https://tools.ietf.org/html/rfc5234) CTL character.
 ///
@@ -104,7 +107,15 @@ fn next_token(source: &[u8]) -> Result<&[u8]> {
 /// Take the next valid [RFC6265](https://tools.ietf.org/html/rfc6265)
 /// cookie-value.
 fn next_cookie_value<'s>(source: &'s [u8]) -> Result<Quotable<'s>> {
-    maybe_quoted(non_zero_length(collect_matching(source, is_cookie_octet))?)
+    // Quotes are not cookie-octets so they are collected around the value.
+    let quoted = source.first() == Some(&b'"');
+    let start = if quoted { 1 } else { 0 };
+    let mut end = start + collect_matching(&source[start..], is_cookie_octet).len();
+    if quoted && source.get(end) == Some(&b'"') {
+        end += 1;
+    }
+
+    maybe_quoted(&source[..end])
 }
 
 /// Take the next cookie fragment.
@@ -121,9 +132,15 @@ fn split_cookie<'s>(source: &'s [u8]) -> Result<(&'s [u8], &'s [u8])> {
 
 /// Process a cookie string into a pair and a set of arguments.
 pub fn process_cookie<'s>(source: &'s str) -> Result<(Pair, ArgumentIter<'s>)> {
+    let (pair, arguments) = process_cookie_ref(source)?;
+    Ok((pair.to_pair(), arguments))
+}
+
+/// Process a cookie string into a borrowed pair and a set of arguments.
+pub fn process_cookie_ref<'s>(source: &'s str) -> Result<(PairRef<'s>, ArgumentIter<'s>)> {
     let (cookie, arguments) = split_cookie(source.as_bytes())?;
     Ok((
-        Pair::from_bytes(cookie)?,
+        PairRef::from_bytes(cookie)?,
         ArgumentIter::new(arguments),
     ))
 }
@@ -131,7 +148,7 @@ pub fn process_cookie<'s>(source: &'s str) -> Result<(Pair, ArgumentIter<'s>)> {
 /// A decoded cookie name=value pair.
 ///
 /// Defaults to an empty string with both the name and value as being empty.
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Pair {
     /// Formated `name=value` pair.
     pair: String,
@@ -144,6 +161,58 @@ pub struct Pair {
 impl Pair {
     /// Create a cookie pair from a byte slice.
     fn from_bytes(source: &[u8]) -> Result<Pair> {
+        PairRef::from_bytes(source).map(|pair| pair.to_pair())
+    }
+
+    /// Get the name of the cookie.
+    pub fn name(&self) -> &str {
+        &self.pair.as_str()[0..self.name_len]
+    }
+
+    /// Get the value of a cookie.
+    pub fn value(&self) -> &str {
+        let (start, length) = self.value_location;
+        &self.pair.as_str()[start..start + length]
+    }
+
+    /// Get the (name, value) pair of a cookie.
+    pub fn as_tuple(&self) -> (&str, &str) {
+        (self.name(), self.value())
+    }
+
+    /// Get the formatted `name=value` pair string of a cookie.
+    pub fn as_str(&self) -> &str {
+        self.pair.as_str()
+    }
+}
+
+impl FromStr for Pair {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Pair> {
+        Pair::from_bytes(source.as_bytes())
+    }
+}
+
+/// A cookie name=value pair borrowed from the string it was parsed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PairRef<'s> {
+    /// Formated `name=value` pair.
+    pair: &'s str,
+    /// The length of the name at the start of the cookie.
+    name_len: usize,
+    /// The start and location of the value of the cookie.
+    value_location: (usize, usize),
+}
+
+impl<'s> PairRef<'s> {
+    /// Parse a cookie pair without copying it.
+    pub fn parse(source: &'s str) -> Result<PairRef<'s>> {
+        PairRef::from_bytes(source.as_bytes())
+    }
+
+    /// Create a borrowed cookie pair from a byte slice.
+    fn from_bytes(source: &'s [u8]) -> Result<PairRef<'s>> {
         let name = next_token(source)?;
         let value_start = name.len() + 1;
         ensure!(
@@ -157,40 +226,96 @@ impl Pair {
             Quotable::Plain(value) => (value_start, value.len()),
         };
 
-        Ok(Pair {
-            pair: from_utf8(source)?.to_string(),
+        Ok(PairRef {
+            pair: from_utf8(source)?,
             name_len: name.len(),
             value_location: value_location,
         })
     }
 
     /// Get the name of the cookie.
-    pub fn name(&self) -> &str {
-        &self.pair.as_str()[0..self.name_len]
+    pub fn name(&self) -> &'s str {
+        &self.pair[0..self.name_len]
     }
 
     /// Get the value of a cookie.
-    pub fn value(&self) -> &str {
+    pub fn value(&self) -> &'s str {
         let (start, length) = self.value_location;
-        &self.pair.as_str()[start..length]
+        &self.pair[start..start + length]
     }
 
     /// Get the (name, value) pair of a cookie.
-    pub fn as_tuple(&self) -> (&str, &str) {
+    pub fn as_tuple(&self) -> (&'s str, &'s str) {
         (self.name(), self.value())
     }
 
     /// Get the formatted `name=value` pair string of a cookie.
+    pub fn as_str(&self) -> &'s str {
+        self.pair
+    }
+
+    /// Copy the pair into an owned pair.
+    pub fn to_pair(&self) -> Pair {
+        Pair {
+            pair: self.pair.to_owned(),
+            name_len: self.name_len,
+            value_location: self.value_location,
+        }
+    }
+}
+
+/// A cookie attribute that is not otherwise understood.
+///
+/// Extensions are either a bare `name` or a `name=value` pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    /// Formatted `name` or `name=value` attribute.
+    attribute: String,
+    /// The length of the name at the start of the attribute.
+    name_len: usize,
+}
+
+impl Extension {
+    /// Create an extension attribute from a byte slice.
+    pub(crate) fn from_bytes(source: &[u8]) -> Result<Extension> {
+        let attribute = next_fragment(source)?;
+        ensure!(attribute.len() == source.len(), ErrorKind::InvalidByte);
+        let name_len = attribute
+            .iter()
+            .position(|&byte| byte == b'=')
+            .unwrap_or(attribute.len());
+
+        Ok(Extension {
+            attribute: from_utf8(attribute)?.to_string(),
+            name_len,
+        })
+    }
+
+    /// Get the name of the attribute.
+    pub fn name(&self) -> &str {
+        &self.attribute[..self.name_len]
+    }
+
+    /// Get the value of the attribute if it has one.
+    pub fn value(&self) -> Option<&str> {
+        if self.name_len < self.attribute.len() {
+            Some(&self.attribute[self.name_len + 1..])
+        } else {
+            None
+        }
+    }
+
+    /// Get the formatted attribute string.
     pub fn as_str(&self) -> &str {
-        self.pair.as_str()
+        self.attribute.as_str()
     }
 }
 
-impl FromStr for Pair {
+impl FromStr for Extension {
     type Err = Error;
 
-    fn from_str(source: &str) -> Result<Pair> {
-        Pair::from_bytes(source.as_bytes())
+    fn from_str(source: &str) -> Result<Extension> {
+        Extension::from_bytes(source.as_bytes())
     }
 }
 
@@ -246,6 +371,8 @@ pub enum Argument<'s> {
     Path(&'s str),
     Secure,
     HttpOnly,
+    SameSite(SameSite),
+    Partitioned,
     Extension(&'s [u8]),
 }
 
@@ -255,8 +382,9 @@ impl<'s> Argument<'s> {
             let time = date::parse(&fragment[8..])?;
             Ok(Argument::Expires(time))
         } else if fragment.starts_with(b"Max-Age=") {
-            let seconds = from_utf8(&fragment[8..])?.parse()?;
-            let duration = Duration::seconds(seconds);
+            // A maximum age of zero or less expires the cookie immediately.
+            let seconds: i64 = from_utf8(&fragment[8..])?.parse()?;
+            let duration = Duration::from_secs(seconds.max(0) as u64);
             Ok(Argument::MaxAge(duration))
         } else if fragment.starts_with(b"Domain=") {
             Ok(Argument::Domain(from_utf8(&fragment[7..])?))
@@ -266,6 +394,14 @@ impl<'s> Argument<'s> {
             Ok(Argument::Secure)
         } else if fragment == b"HttpOnly" {
             Ok(Argument::HttpOnly)
+        } else if fragment == b"Partitioned" {
+            Ok(Argument::Partitioned)
+        } else if fragment.starts_with(b"SameSite=") {
+            // Unrecognised enforcement modes are treated as unknown attributes.
+            match SameSite::from_bytes(&fragment[9..]) {
+                Some(same_site) => Ok(Argument::SameSite(same_site)),
+                None => Ok(Argument::Extension(fragment)),
+            }
         } else {
             Ok(Argument::Extension(fragment))
         }
@@ -275,7 +411,7 @@ impl<'s> Argument<'s> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use time::strptime;
+    use time::{at_utc, strptime};
 
     #[test]
     fn maybe_quoted() {
@@ -290,6 +426,27 @@ mod test {
         }
     }
 
+    #[test]
+    fn pair_values() {
+        let checks = [
+            ("key=value", "key", "value"),
+            ("$=}", "$", "}"),
+            ("!=#", "!", "#"),
+            ("key=\"quoted\"", "key", "quoted"),
+            ("key=\"\"", "key", ""),
+            ("key=", "key", ""),
+        ];
+
+        for &(pair, name, value) in checks.iter() {
+            let pair: Pair = pair.parse().unwrap();
+            assert_eq!(pair.as_tuple(), (name, value));
+        }
+
+        for pair in ["key=\"open", "key=\"", "key", "=value"].iter() {
+            assert!(pair.parse::<Pair>().is_err(), "{:?} should not parse", pair);
+        }
+    }
+
     #[test]
     fn extract_tokens() {
         let token = next_token(b"key=value").unwrap();
@@ -317,13 +474,35 @@ mod test {
         let expected_args = vec![
             Argument::Extension(b"fragment"),
             Argument::Domain("google.com"),
-            Argument::Expires(
-                strptime("Sun Feb 25 01:36:48 UTC 2018", "%a %b %d %H:%M:%S UTC %Y").unwrap(),
-            ),
-            Argument::MaxAge(Duration::seconds(3200)),
+            Argument::Expires(at_utc(
+                strptime("Sun Feb 25 01:36:48 UTC 2018", "%a %b %d %H:%M:%S UTC %Y")
+                    .unwrap()
+                    .to_timespec(),
+            )),
+            Argument::MaxAge(Duration::from_secs(3200)),
             Argument::Extension(b"other=fragment"),
         ];
         assert_eq!(cookie, expected_cookie);
         assert_eq!(args, expected_args);
     }
+
+    #[test]
+    fn extensions() {
+        let checks = &[
+            ("Experimental", "Experimental", None),
+            ("Priority=High", "Priority", Some("High")),
+            ("x-vendor=a=b", "x-vendor", Some("a=b")),
+            ("Empty=", "Empty", Some("")),
+        ];
+
+        for &(text, name, value) in checks {
+            let extension: Extension = text.parse().unwrap();
+            assert_eq!(extension.name(), name);
+            assert_eq!(extension.value(), value);
+            assert_eq!(extension.as_str(), text);
+        }
+
+        assert!("Bad; Extension".parse::<Extension>().is_err());
+        assert!("".parse::<Extension>().is_err());
+    }
 }
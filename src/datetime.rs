@@ -0,0 +1,177 @@
+//! Conversions between the times used by cookies and other representations.
+//!
+//! Cookie times are represented with `std::time::SystemTime`. With the `chrono` feature, times
+//! can also be read as `chrono::DateTime<Utc>` and `chrono::Utc` can be used as the clock of a
+//! jar. The `time03` feature does the same for `time::OffsetDateTime` from version 0.3 of the
+//! `time` crate. Any type that converts into a `SystemTime`, which includes the date times of
+//! both crates, can be given to the [`Builder`](../struct.Builder.html).
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use time::{at_utc, Timespec, Tm};
+
+use ::cookie::Expires;
+
+/// Get the number of whole seconds and nanoseconds since the Unix epoch.
+///
+/// Times before the epoch have a negative number of seconds and a positive number of
+/// nanoseconds.
+fn unix_parts(time: SystemTime) -> (i64, u32) {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+        Err(error) => {
+            let before = error.duration();
+            match before.subsec_nanos() {
+                0 => (-(before.as_secs() as i64), 0),
+                nanos => (-(before.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+            }
+        }
+    }
+}
+
+/// Get the time a number of seconds and nanoseconds after the Unix epoch.
+fn from_unix_parts(seconds: i64, nanoseconds: u32) -> SystemTime {
+    let nanoseconds = Duration::new(0, nanoseconds);
+    if seconds >= 0 {
+        UNIX_EPOCH + Duration::from_secs(seconds as u64) + nanoseconds
+    } else {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs()) + nanoseconds
+    }
+}
+
+/// Get the number of whole seconds since the Unix epoch.
+pub(crate) fn unix_seconds(time: SystemTime) -> i64 {
+    unix_parts(time).0
+}
+
+/// Get the time a number of seconds after the Unix epoch.
+pub(crate) fn from_unix_seconds(seconds: i64) -> SystemTime {
+    from_unix_parts(seconds, 0)
+}
+
+/// Get the time a number of microseconds after the Unix epoch.
+#[cfg(any(feature = "firefox", feature = "chromium"))]
+pub(crate) fn from_unix_micros(micros: i64) -> SystemTime {
+    let seconds = micros.div_euclid(1_000_000);
+    let nanoseconds = micros.rem_euclid(1_000_000) * 1_000;
+    from_unix_parts(seconds, nanoseconds as u32)
+}
+
+/// Convert a broken down UTC time into a system time.
+pub(crate) fn from_tm(time: &Tm) -> SystemTime {
+    let time = time.to_timespec();
+    from_unix_parts(time.sec, time.nsec as u32)
+}
+
+/// Convert a system time into a broken down UTC time.
+pub(crate) fn to_tm(time: SystemTime) -> Tm {
+    let (seconds, nanoseconds) = unix_parts(time);
+    at_utc(Timespec::new(seconds, nanoseconds as i32))
+}
+
+impl From<SystemTime> for Expires {
+    fn from(time: SystemTime) -> Expires {
+        Expires::AtUtc(time)
+    }
+}
+
+impl Expires {
+    /// Get the time at which the cookie expires.
+    ///
+    /// None if the cookie never expires.
+    pub fn time(&self) -> Option<SystemTime> {
+        match *self {
+            Expires::AtUtc(time) => Some(time),
+            Expires::Never => None,
+        }
+    }
+
+    /// Get the time at which the cookie expires as a `chrono` date time.
+    #[cfg(feature = "chrono")]
+    pub fn to_chrono(&self) -> Option<::chrono::DateTime<::chrono::Utc>> {
+        self.time().map(Into::into)
+    }
+
+    /// Get the time at which the cookie expires as a `time` 0.3 date time.
+    #[cfg(feature = "time03")]
+    pub fn to_offset_date_time(&self) -> Option<::time03::OffsetDateTime> {
+        self.time().map(Into::into)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: ::chrono::TimeZone> From<::chrono::DateTime<Tz>> for Expires {
+    fn from(time: ::chrono::DateTime<Tz>) -> Expires {
+        Expires::AtUtc(time.into())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ::jar::Clock for ::chrono::Utc {
+    fn now(&self) -> SystemTime {
+        ::chrono::Utc::now().into()
+    }
+}
+
+#[cfg(feature = "time03")]
+impl From<::time03::OffsetDateTime> for Expires {
+    fn from(time: ::time03::OffsetDateTime) -> Expires {
+        Expires::AtUtc(time.into())
+    }
+}
+
+/// A function that produces the current time as a `time` 0.3 date time.
+#[cfg(feature = "time03")]
+pub type OffsetDateTimeFn = fn() -> ::time03::OffsetDateTime;
+
+#[cfg(feature = "time03")]
+impl ::jar::Clock for OffsetDateTimeFn {
+    fn now(&self) -> SystemTime {
+        self().into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unix_times() {
+        for &(seconds, nanoseconds) in &[(0, 0), (1_500_000_000, 250), (-1, 0), (-2, 999_999_999)] {
+            let time = from_unix_parts(seconds, nanoseconds);
+            assert_eq!(unix_parts(time), (seconds, nanoseconds));
+            assert_eq!(from_tm(&to_tm(time)), time);
+        }
+
+        assert_eq!(unix_seconds(from_unix_seconds(-86_400)), -86_400);
+    }
+
+    #[cfg(any(feature = "firefox", feature = "chromium"))]
+    #[test]
+    fn unix_micros() {
+        assert_eq!(from_unix_micros(-1), from_unix_parts(-1, 999_999_000));
+        assert_eq!(from_unix_micros(1_500_000), from_unix_parts(1, 500_000_000));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_times() {
+        use chrono::{TimeZone, Utc};
+
+        let time = Utc.timestamp_opt(1_500_000_000, 0).unwrap();
+        let expires = Expires::from(time);
+        assert_eq!(expires, Expires::AtUtc(from_unix_seconds(1_500_000_000)));
+        assert_eq!(expires.to_chrono(), Some(time));
+    }
+
+    #[cfg(feature = "time03")]
+    #[test]
+    fn time03_times() {
+        use time03::OffsetDateTime;
+
+        let time = OffsetDateTime::from_unix_timestamp(1_500_000_000).unwrap();
+        let expires = Expires::from(time);
+        assert_eq!(expires, Expires::AtUtc(from_unix_seconds(1_500_000_000)));
+        assert_eq!(expires.to_offset_date_time(), Some(time));
+    }
+}
@@ -10,7 +10,10 @@ error_chain!{
 
     // Links to other standard errors.
     foreign_links {
+        Io(::std::io::Error);
         Url(::url::ParseError);
+        Sqlite(::rusqlite::Error) #[cfg(any(feature = "firefox", feature = "chromium"))];
+        HeaderValue(::http::header::InvalidHeaderValue) #[cfg(feature = "http")];
     }
 
     // Internal error forms.
@@ -32,6 +35,27 @@ error_chain!{
         HostInvalid {
             description("Invalid to provide an IP address for a SetCookie")
         }
+        InsecurePartitioned {
+            description("A partitioned cookie must also be secure")
+        }
+        EncryptedValue {
+            description("The value of the cookie is encrypted")
+        }
+        MissingHeader {
+            description("The saved jar does not start with a format header")
+        }
+        UnsupportedVersion(version: i64) {
+            description("The saved jar uses an unsupported format version"),
+            display("UnsupportedVersion({})", version),
+        }
+        MalformedEntry(reason: &'static str) {
+            description("An entry of the saved jar was malformed"),
+            display("MalformedEntry({})", reason),
+        }
+        ShortKey(length: usize) {
+            description("The key is too short to protect cookies"),
+            display("ShortKey({})", length),
+        }
     }
 }
 
@@ -0,0 +1,14 @@
+//! Entry points into the internal parsers for the fuzzing harnesses in `fuzz/`.
+//!
+//! This module only exists when built with `cargo fuzz`.
+
+use std::time::SystemTime;
+
+use ::cookie::parse::date;
+use ::datetime::from_tm;
+use ::error::parser::Result;
+
+/// Parse the value of an `Expires` attribute.
+pub fn parse_date(source: &[u8]) -> Result<SystemTime> {
+    date::parse(source).map(|time| from_tm(&time))
+}
@@ -0,0 +1,204 @@
+//! Exchange of cookies with the types of the `http` crate.
+//!
+//! With the `http` feature a [`Jar`](struct.Jar.html) can store the cookies from the
+//! `Set-Cookie` headers of an `http::Response` and add a `Cookie` header to an `http::Request`.
+//!
+//! Header values are not required to be UTF-8. A `Set-Cookie` header that is not valid UTF-8 is
+//! skipped and reported like any other malformed cookie, while an existing `Cookie` header on a
+//! request is extended byte for byte so that the rest of its contents are kept intact.
+
+use std::str;
+
+use http::header::{HeaderMap, HeaderValue, COOKIE, SET_COOKIE};
+use http::{Request, Response, Uri};
+use url::Url;
+
+use ::cookie::Cookie;
+use ::error::*;
+use ::jar::{Clock, Jar};
+use ::store::CookieStore;
+
+/// Convert the absolute URI of a request into a URL.
+pub(crate) fn url_from_uri(uri: &Uri) -> Result<Url> {
+    Ok(Url::parse(&uri.to_string())?)
+}
+
+/// Get the name from the bytes of a `name=value` pair.
+fn pair_name(pair: &[u8]) -> &[u8] {
+    match pair.iter().position(|&byte| byte == b'=') {
+        Some(end) => &pair[..end],
+        None => pair,
+    }
+}
+
+/// Parse the value of a single `Set-Cookie` header.
+pub(crate) fn parse_set_cookie(value: &HeaderValue, url: &Url) -> Result<Cookie> {
+    let value = str::from_utf8(value.as_bytes()).map_err(parser::Error::from)?;
+    Cookie::parse(value, url)
+}
+
+impl<T: Clock, S: CookieStore> Jar<T, S> {
+    /// Store the cookies from all of the `Set-Cookie` headers received from a URL.
+    ///
+    /// The top-level site is the URL of the page being viewed when the headers were received.
+    ///
+    /// Headers that do not hold a valid cookie are skipped and their errors returned.
+    pub fn store_headers(&mut self, url: &Url, headers: &HeaderMap, top_level_site: Option<&Url>)
+        -> Vec<Error>
+    {
+        let mut errors = Vec::new();
+
+        for value in headers.get_all(SET_COOKIE) {
+            match parse_set_cookie(value, url) {
+                Ok(cookie) => {
+                    self.add_cookie(cookie, top_level_site);
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+
+        errors
+    }
+
+    /// Store the cookies from a response to a request for a URI.
+    ///
+    /// The request URI must be absolute. Headers that do not hold a valid cookie are skipped and
+    /// their errors returned.
+    pub fn store_response<B>(
+        &mut self,
+        request_uri: &Uri,
+        response: &Response<B>,
+        top_level_site: Option<&Url>,
+    ) -> Result<Vec<Error>> {
+        let url = url_from_uri(request_uri)?;
+        Ok(self.store_headers(&url, response.headers(), top_level_site))
+    }
+
+    /// Add the cookies for a request to a URL to its `Cookie` header.
+    ///
+    /// The cookies are appended to an existing `Cookie` header, replacing any pairs in it with the
+    /// same names as those from the jar. The header is left unchanged if no cookies match.
+    pub fn write_headers(&self, url: &Url, headers: &mut HeaderMap, top_level_site: Option<&Url>)
+        -> Result<()>
+    {
+        let cookies = match self.cookie_header(url, top_level_site) {
+            Some(cookies) => cookies,
+            None => return Ok(()),
+        };
+
+        let value = match headers.get(COOKIE) {
+            Some(existing) => {
+                let supplied: Vec<_> = cookies
+                    .split("; ")
+                    .map(|pair| pair_name(pair.as_bytes()))
+                    .collect();
+                let mut value = Vec::new();
+                for pair in existing.as_bytes().split(|&byte| byte == b';') {
+                    let pair = pair.trim_ascii();
+                    if !pair.is_empty() && !supplied.contains(&pair_name(pair)) {
+                        value.extend_from_slice(pair);
+                        value.extend_from_slice(b"; ");
+                    }
+                }
+                value.extend_from_slice(cookies.as_bytes());
+                HeaderValue::from_bytes(&value)?
+            }
+            None => HeaderValue::from_str(&cookies)?,
+        };
+
+        headers.insert(COOKIE, value);
+        Ok(())
+    }
+
+    /// Add the cookies for a request to its `Cookie` header.
+    ///
+    /// The URI of the request must be absolute.
+    pub fn write_request<B>(&self, request: &mut Request<B>, top_level_site: Option<&Url>)
+        -> Result<()>
+    {
+        let url = url_from_uri(request.uri())?;
+        self.write_headers(&url, request.headers_mut(), top_level_site)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jar::ClockFn;
+
+    fn response(set_cookies: &[&[u8]]) -> Response<()> {
+        let mut response = Response::new(());
+        for set_cookie in set_cookies {
+            response
+                .headers_mut()
+                .append(SET_COOKIE, HeaderValue::from_bytes(set_cookie).unwrap());
+        }
+        response
+    }
+
+    #[test]
+    fn store_response() {
+        let mut jar: Jar<ClockFn> = Jar::default();
+        let uri: Uri = "https://www.example.com/account/login".parse().unwrap();
+        let response = response(&[
+            b"SID=31d4d96e407aad42; Path=/; Secure",
+            b"lang=en-US; Domain=example.com",
+            b"name=caf\xe9",
+            b"=",
+        ]);
+
+        let errors = jar.store_response(&uri, &response, None).expect("Could not store cookies");
+        assert_eq!(errors.len(), 2);
+        match errors[0] {
+            Error(ErrorKind::CookieParse(parser::ErrorKind::Utf8(_)), _) => {}
+            ref other => panic!("Expected a UTF-8 error, got {:?}", other),
+        }
+
+        let url = "https://www.example.com/account/".parse().unwrap();
+        let header = jar.cookie_header(&url, None);
+        assert_eq!(header.as_deref(), Some("lang=en-US; SID=31d4d96e407aad42"));
+    }
+
+    #[test]
+    fn relative_uri() {
+        let mut jar: Jar<ClockFn> = Jar::default();
+        let uri: Uri = "/account/login".parse().unwrap();
+
+        match jar.store_response(&uri, &response(&[b"SID=1"]), None) {
+            Err(Error(ErrorKind::Url(_), _)) => {}
+            other => panic!("Expected a URL error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn write_request() {
+        let mut jar: Jar<ClockFn> = Jar::default();
+        let origin = "https://www.example.com/".parse().unwrap();
+        jar.add_cookie(Cookie::parse("SID=31d4d96e407aad42", &origin).unwrap(), None);
+
+        let mut request = Request::get("https://www.example.com/index.html").body(()).unwrap();
+        jar.write_request(&mut request, None).expect("Could not write cookies");
+        assert_eq!(request.headers()[COOKIE], "SID=31d4d96e407aad42");
+
+        // An existing header is kept even when it is not UTF-8.
+        let mut request = Request::get("https://www.example.com/")
+            .header(COOKIE, HeaderValue::from_bytes(b"name=caf\xe9").unwrap())
+            .body(())
+            .unwrap();
+        jar.write_request(&mut request, None).expect("Could not write cookies");
+        let expected = &b"name=caf\xe9; SID=31d4d96e407aad42"[..];
+        assert_eq!(request.headers()[COOKIE].as_bytes(), expected);
+
+        // Pairs for the same cookies are replaced rather than repeated.
+        let mut request = Request::get("https://www.example.com/")
+            .header(COOKIE, "SID=stale; lang=en-US")
+            .body(())
+            .unwrap();
+        jar.write_request(&mut request, None).expect("Could not write cookies");
+        assert_eq!(request.headers()[COOKIE], "lang=en-US; SID=31d4d96e407aad42");
+
+        let mut request = Request::get("https://other.example/").body(()).unwrap();
+        jar.write_request(&mut request, None).expect("Could not write cookies");
+        assert!(request.headers().get(COOKIE).is_none());
+    }
+}
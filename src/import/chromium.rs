@@ -0,0 +1,245 @@
+//! Import of cookies from Chromium.
+//!
+//! Chromium stores its cookies in the `cookies` table of the `Cookies` database within each
+//! profile directory. Only unencrypted values can be imported; cookies that only have an
+//! `encrypted_value` are skipped.
+
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags, Row};
+
+use ::cookie::{Builder, Cookie, SameSite};
+use ::datetime::from_unix_micros;
+use ::error::*;
+use ::jar::{Clock, Jar};
+use ::store::CookieStore;
+use super::Report;
+
+/// Columns read from the `cookies` table.
+const QUERY: &str = "\
+    SELECT host_key, name, value, length(encrypted_value), path, expires_utc, is_secure, \
+           is_httponly, samesite, has_expires, is_persistent, creation_utc, last_access_utc \
+    FROM cookies";
+
+/// Microseconds between the Windows epoch (1601-01-01) and the Unix epoch (1970-01-01).
+const WINDOWS_EPOCH_OFFSET: i64 = 11_644_473_600_000_000;
+
+impl<T: Clock, S: CookieStore> Jar<T, S> {
+    /// Add all of the cookies from a Chromium `Cookies` database to the jar.
+    ///
+    /// Rows that do not form valid cookies, including those with encrypted values, are skipped
+    /// and listed in the report. Expired cookies and those rejected by the policy of the jar are
+    /// not counted as imported.
+    pub fn import_chromium<P: AsRef<Path>>(&mut self, path: P) -> Result<Report> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut statement = connection.prepare(QUERY)?;
+        let mut rows = statement.query([])?;
+        let mut report = Report::default();
+
+        while let Some(row) = rows.next()? {
+            let row = ChromiumCookie::from_row(row)?;
+            match row.to_cookie() {
+                Ok(cookie) => {
+                    if self.add_cookie(cookie, None) {
+                        report.imported += 1;
+                    }
+                }
+                Err(error) => report.skip(row.host_key, row.name, error),
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// A single row of the `cookies` table.
+struct ChromiumCookie {
+    /// Host for host-only cookies or the domain prefixed with a `.` for domain cookies.
+    host_key: String,
+    name: String,
+    value: String,
+    /// Length of the encrypted value in bytes.
+    encrypted_len: i64,
+    path: String,
+    /// Microseconds since the Windows epoch.
+    expires_utc: i64,
+    secure: bool,
+    http_only: bool,
+    /// `-1` when unspecified, `0` when unrestricted, `1` for lax and `2` for strict.
+    same_site: i64,
+    has_expires: bool,
+    persistent: bool,
+    /// Microseconds since the Windows epoch.
+    creation_utc: i64,
+    /// Microseconds since the Windows epoch.
+    last_access_utc: i64,
+}
+
+impl ChromiumCookie {
+    /// Read the columns selected by `QUERY`.
+    fn from_row(row: &Row) -> ::rusqlite::Result<ChromiumCookie> {
+        Ok(ChromiumCookie {
+            host_key: row.get(0)?,
+            name: row.get(1)?,
+            value: row.get(2)?,
+            encrypted_len: row.get::<_, Option<i64>>(3)?.unwrap_or(0),
+            path: row.get(4)?,
+            expires_utc: row.get(5)?,
+            secure: row.get(6)?,
+            http_only: row.get(7)?,
+            same_site: row.get(8)?,
+            has_expires: row.get(9)?,
+            persistent: row.get(10)?,
+            creation_utc: row.get(11)?,
+            last_access_utc: row.get(12)?,
+        })
+    }
+
+    /// Build the cookie described by the row.
+    fn to_cookie(&self) -> Result<Cookie> {
+        ensure!(
+            !(self.value.is_empty() && self.encrypted_len > 0),
+            ErrorKind::EncryptedValue
+        );
+
+        let builder = if self.host_key.starts_with('.') {
+            Builder::new().domain(&self.host_key[1..])
+        } else {
+            Builder::new().host_str(&self.host_key)
+        };
+
+        let mut builder = builder
+            .path(&self.path)
+            .pair_str(&format!("{}={}", self.name, self.value))
+            .secure(self.secure)
+            .http_only(self.http_only)
+            .creation_time(from_unix_micros(self.creation_utc - WINDOWS_EPOCH_OFFSET))
+            .last_access_time(from_unix_micros(self.last_access_utc - WINDOWS_EPOCH_OFFSET));
+
+        // Session cookies are left without an expiry.
+        if self.has_expires && self.persistent {
+            builder = builder.expiry(from_unix_micros(self.expires_utc - WINDOWS_EPOCH_OFFSET));
+        }
+
+        let builder = match self.same_site {
+            0 => builder.same_site(SameSite::None),
+            1 => builder.same_site(SameSite::Lax),
+            2 => builder.same_site(SameSite::Strict),
+            _ => builder,
+        };
+
+        builder.build_cookie()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cookie::Expires;
+    use jar::ClockFn;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// A fixed time before any of the cookies in the fixture expire.
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    #[test]
+    fn import_fixture() {
+        let fixture = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/chromium-cookies.sqlite"
+        );
+        let mut jar = Jar::with_clock(now as ClockFn);
+        let report = jar.import_chromium(fixture).expect("Could not import fixture");
+
+        assert_eq!(report.imported(), 3);
+        assert_eq!(report.skipped().len(), 1);
+        assert_eq!(report.skipped()[0].name(), "secret");
+        match *report.skipped()[0].error() {
+            Error(ErrorKind::EncryptedValue, _) => {}
+            ref other => panic!("Expected an encrypted value error, got {:?}", other),
+        }
+
+        let checks = [
+            ("https://www.example.com/account/", vec!["SID", "lang"]),
+            ("https://sub.example.com/", vec!["SID"]),
+            ("http://127.0.0.1/", vec!["local"]),
+        ];
+
+        for &(url, ref expected) in checks.iter() {
+            let url = url.parse().unwrap();
+            let mut names: Vec<_> = jar
+                .url_matches(&url, None)
+                .map(|cookie| cookie.pair().name().to_owned())
+                .collect();
+            names.sort();
+            assert_eq!(&names, expected);
+        }
+    }
+
+    #[test]
+    fn rejected_not_imported() {
+        use policy::{BlockDomains, Domains};
+
+        let fixture = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/chromium-cookies.sqlite"
+        );
+        let blocked = Domains::parse(vec!["example.com"]).unwrap();
+        let mut jar = Jar::with_clock(now as ClockFn).policy(BlockDomains(blocked));
+        let report = jar.import_chromium(fixture).expect("Could not import fixture");
+
+        assert_eq!(report.imported(), 1);
+        assert_eq!(report.skipped().len(), 1);
+    }
+
+    #[test]
+    fn row_attributes() {
+        let row = ChromiumCookie {
+            host_key: ".example.com".to_owned(),
+            name: "SID".to_owned(),
+            value: "31d4d96e407aad42".to_owned(),
+            encrypted_len: 0,
+            path: "/".to_owned(),
+            expires_utc: 13_644_473_600_000_000,
+            secure: true,
+            http_only: true,
+            same_site: 1,
+            has_expires: true,
+            persistent: true,
+            creation_utc: 13_150_473_600_000_000,
+            last_access_utc: 13_250_473_600_500_000,
+        };
+        let cookie = row.to_cookie().expect("Could not build cookie");
+
+        assert_eq!(cookie.domain(), Some("example.com"));
+        assert!(!cookie.host_only());
+        assert!(cookie.secure());
+        assert!(cookie.http_only());
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(
+            *cookie.expiry(),
+            Expires::AtUtc(UNIX_EPOCH + Duration::new(2_000_000_000, 0))
+        );
+        assert_eq!(
+            cookie.creation_time(),
+            Some(UNIX_EPOCH + Duration::new(1_506_000_000, 0))
+        );
+        assert_eq!(
+            cookie.last_access_time(),
+            Some(UNIX_EPOCH + Duration::new(1_606_000_000, 500_000_000))
+        );
+
+        let session = ChromiumCookie {
+            has_expires: false,
+            persistent: false,
+            same_site: -1,
+            ..
+            row
+        };
+        let cookie = session.to_cookie().expect("Could not build cookie");
+        assert_eq!(cookie.expiry(), &Expires::Never);
+        assert_eq!(cookie.same_site(), None);
+    }
+}
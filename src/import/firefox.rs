@@ -0,0 +1,198 @@
+//! Import of cookies from Firefox.
+//!
+//! Firefox stores its cookies in the `moz_cookies` table of `cookies.sqlite` within each profile
+//! directory. The database is locked while Firefox is running so a copy should be imported
+//! instead.
+
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags, Row};
+
+use ::cookie::{Builder, Cookie, SameSite};
+use ::datetime::{from_unix_micros, from_unix_seconds};
+use ::error::*;
+use ::jar::{Clock, Jar};
+use ::store::CookieStore;
+use super::Report;
+
+/// Columns read from the `moz_cookies` table.
+const QUERY: &str = "\
+    SELECT host, name, value, path, expiry, isSecure, isHttpOnly, sameSite, creationTime, \
+           lastAccessed \
+    FROM moz_cookies";
+
+impl<T: Clock, S: CookieStore> Jar<T, S> {
+    /// Add all of the cookies from a Firefox `cookies.sqlite` database to the jar.
+    ///
+    /// Rows that do not form valid cookies are skipped and listed in the report. Expired cookies
+    /// and those rejected by the policy of the jar are not counted as imported.
+    pub fn import_firefox<P: AsRef<Path>>(&mut self, path: P) -> Result<Report> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut statement = connection.prepare(QUERY)?;
+        let mut rows = statement.query([])?;
+        let mut report = Report::default();
+
+        while let Some(row) = rows.next()? {
+            let row = MozCookie::from_row(row)?;
+            match row.to_cookie() {
+                Ok(cookie) => {
+                    if self.add_cookie(cookie, None) {
+                        report.imported += 1;
+                    }
+                }
+                Err(error) => report.skip(row.host, row.name, error),
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// A single row of the `moz_cookies` table.
+struct MozCookie {
+    /// Host for host-only cookies or the domain prefixed with a `.` for domain cookies.
+    host: String,
+    name: String,
+    value: String,
+    path: String,
+    /// Seconds since the Unix epoch.
+    expiry: i64,
+    secure: bool,
+    http_only: bool,
+    /// `0` when unrestricted or unspecified, `1` for lax and `2` for strict.
+    same_site: i64,
+    /// Microseconds since the Unix epoch.
+    creation_time: i64,
+    /// Microseconds since the Unix epoch.
+    last_accessed: i64,
+}
+
+impl MozCookie {
+    /// Read the columns selected by `QUERY`.
+    fn from_row(row: &Row) -> ::rusqlite::Result<MozCookie> {
+        Ok(MozCookie {
+            host: row.get(0)?,
+            name: row.get(1)?,
+            value: row.get(2)?,
+            path: row.get(3)?,
+            expiry: row.get(4)?,
+            secure: row.get(5)?,
+            http_only: row.get(6)?,
+            same_site: row.get(7)?,
+            creation_time: row.get(8)?,
+            last_accessed: row.get(9)?,
+        })
+    }
+
+    /// Build the cookie described by the row.
+    fn to_cookie(&self) -> Result<Cookie> {
+        let builder = if self.host.starts_with('.') {
+            Builder::new().domain(&self.host[1..])
+        } else {
+            Builder::new().host_str(&self.host)
+        };
+
+        let builder = builder
+            .path(&self.path)
+            .pair_str(&format!("{}={}", self.name, self.value))
+            .expiry(from_unix_seconds(self.expiry))
+            .secure(self.secure)
+            .http_only(self.http_only)
+            .creation_time(from_unix_micros(self.creation_time))
+            .last_access_time(from_unix_micros(self.last_accessed));
+
+        let builder = match self.same_site {
+            1 => builder.same_site(SameSite::Lax),
+            2 => builder.same_site(SameSite::Strict),
+            _ => builder,
+        };
+
+        builder.build_cookie()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jar::ClockFn;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// A fixed time before any of the cookies in the fixture expire.
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    #[test]
+    fn import_fixture() {
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/firefox-cookies.sqlite");
+        let mut jar = Jar::with_clock(now as ClockFn);
+        let report = jar.import_firefox(fixture).expect("Could not import fixture");
+
+        assert_eq!(report.imported(), 3);
+        assert_eq!(report.skipped().len(), 1);
+        assert_eq!(report.skipped()[0].host(), "www.example.com");
+        assert_eq!(report.skipped()[0].name(), "bad name");
+
+        let checks = [
+            ("https://www.example.com/account/", vec!["SID", "lang"]),
+            ("https://sub.example.com/account/", vec!["SID"]),
+            ("https://example.com/", vec!["SID"]),
+            ("http://127.0.0.1/", vec!["local"]),
+        ];
+
+        for &(url, ref expected) in checks.iter() {
+            let url = url.parse().unwrap();
+            let mut names: Vec<_> = jar
+                .url_matches(&url, None)
+                .map(|cookie| cookie.pair().name().to_owned())
+                .collect();
+            names.sort();
+            assert_eq!(&names, expected);
+        }
+    }
+
+    #[test]
+    fn rejected_not_imported() {
+        use policy::{BlockDomains, Domains};
+
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/firefox-cookies.sqlite");
+        let blocked = Domains::parse(vec!["example.com"]).unwrap();
+        let mut jar = Jar::with_clock(now as ClockFn).policy(BlockDomains(blocked));
+        let report = jar.import_firefox(fixture).expect("Could not import fixture");
+
+        assert_eq!(report.imported(), 1);
+        assert_eq!(report.skipped().len(), 1);
+    }
+
+    #[test]
+    fn row_attributes() {
+        let row = MozCookie {
+            host: ".example.com".to_owned(),
+            name: "SID".to_owned(),
+            value: "31d4d96e407aad42".to_owned(),
+            path: "/".to_owned(),
+            expiry: 2_000_000_000,
+            secure: true,
+            http_only: true,
+            same_site: 2,
+            creation_time: 1_500_000_000_000_000,
+            last_accessed: 1_600_000_000_500_000,
+        };
+        let cookie = row.to_cookie().expect("Could not build cookie");
+
+        assert_eq!(cookie.domain(), Some("example.com"));
+        assert!(!cookie.host_only());
+        assert!(cookie.secure());
+        assert!(cookie.http_only());
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+        assert_eq!(cookie.pair().as_tuple().0, "SID");
+        assert_eq!(
+            cookie.creation_time(),
+            Some(UNIX_EPOCH + Duration::new(1_500_000_000, 0))
+        );
+        assert_eq!(
+            cookie.last_access_time(),
+            Some(UNIX_EPOCH + Duration::new(1_600_000_000, 500_000_000))
+        );
+    }
+}
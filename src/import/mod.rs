@@ -0,0 +1,72 @@
+//! Import of cookies stored by other user agents.
+//!
+//! Each source is enabled by its own cargo feature and adds an `import_*` method to the
+//! [`Jar`](../struct.Jar.html).
+
+#[cfg(feature = "chromium")]
+mod chromium;
+#[cfg(feature = "firefox")]
+mod firefox;
+
+use ::error::Error;
+
+/// The outcome of importing cookies into a jar.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The number of cookies added to the jar.
+    imported: usize,
+
+    /// The cookies that could not be imported.
+    skipped: Vec<Skipped>,
+}
+
+impl Report {
+    /// Get the number of cookies added to the jar.
+    pub fn imported(&self) -> usize {
+        self.imported
+    }
+
+    /// Get the cookies that could not be imported.
+    pub fn skipped(&self) -> &[Skipped] {
+        &self.skipped
+    }
+
+    /// Record a cookie that could not be imported.
+    fn skip(&mut self, host: String, name: String, error: Error) {
+        self.skipped.push(Skipped {
+            host,
+            name,
+            error,
+        });
+    }
+}
+
+/// A cookie that could not be imported.
+#[derive(Debug)]
+pub struct Skipped {
+    /// The host or domain the cookie was stored for.
+    host: String,
+
+    /// The name of the cookie.
+    name: String,
+
+    /// The reason the cookie was skipped.
+    error: Error,
+}
+
+impl Skipped {
+    /// Get the host or domain the cookie was stored for.
+    pub fn host(&self) -> &str {
+        self.host.as_str()
+    }
+
+    /// Get the name of the cookie.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Get the reason the cookie was skipped.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+}
@@ -6,51 +6,156 @@
 //!
 //! The jar is also responsible for managing the expiry of cookies and expunging cookies.
 //!
-//! The jar is structured as a tree representing the domains the the paths for which it has
-//! stored values. The jar contains a root domain which branches out into its
-//! subdomains. Similarly, each domain contains its root path which branches out into its
-//! sub-paths.
+//! The cookies themselves are kept in a [`CookieStore`](store/trait.CookieStore.html), which by
+//! default is an in-memory tree of the domains and paths for which the jar has stored values.
 
-use std::collections::HashMap;
-use std::iter;
-use std::net::IpAddr;
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Deref;
+use std::time::SystemTime;
 
 use url::{Url, Host};
-use time::{Tm, now_utc};
 
-use ::cookie::{Cookie, Attributes, Pair, url_dir_path};
+use ::cookie::{Attributes, Cookie, Pair};
+use ::policy::{Context, Decision, Policies, Policy};
+use ::site::{borrow_host, registrable, Site};
+use ::store::{CookieStore, StoredCookies, TreeStore};
 
 /// Something that produces the current UTC time.
 pub trait Clock {
 
     /// Get the current UTC time.
-    fn now(&self) -> Tm;
+    fn now(&self) -> SystemTime;
 }
 
-/// A function that produces the current time in UTC.
-pub type ClockFn = fn() -> Tm;
+/// A function that produces the current time.
+pub type ClockFn = fn() -> SystemTime;
 
 impl Clock for ClockFn {
-    fn now(&self) -> Tm {
+    fn now(&self) -> SystemTime {
         self()
     }
 }
 
+/// A change to the cookies held by a jar.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Event<'c> {
+    /// A cookie was added that did not replace another.
+    Inserted(&'c Cookie),
+    /// A cookie was added that replaced one with the same host, path and name.
+    Replaced {
+        /// The cookie that was replaced.
+        old: &'c Cookie,
+        /// The cookie that replaced it.
+        new: &'c Cookie,
+    },
+    /// A cookie was removed as it had expired or was replaced by an expired cookie.
+    Expired(&'c Cookie),
+    /// A cookie was removed to keep the jar within its limit.
+    Evicted(&'c Cookie),
+    /// A cookie was explicitly removed.
+    Removed(&'c Cookie),
+}
+
+/// A stored cookie that matched a URL.
+///
+/// This borrows the cookie from the jar when its store can lend it out and otherwise holds a copy
+/// of the cookie.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<'j> {
+    cookie: Cow<'j, Cookie>,
+}
+
+impl<'j> Match<'j> {
+    /// Get the domain or host the cookie applies to.
+    pub fn host(&self) -> &Host {
+        self.cookie.host()
+    }
+
+    /// Get the domain name associated with the cookie.
+    ///
+    /// None if the cookie is host-only for an IP address.
+    pub fn domain(&self) -> Option<&str> {
+        match *self.host() {
+            Host::Domain(ref host) => Some(host),
+            _ => None,
+        }
+    }
+
+    /// Get the path the cookie applies to.
+    pub fn path(&self) -> &str {
+        self.cookie.path()
+    }
+
+    /// Get the attributes of the cookie.
+    pub fn attributes(&self) -> &Attributes {
+        &self.cookie
+    }
+
+    /// Get the name and value of the cookie.
+    pub fn pair(&self) -> &Pair {
+        self.cookie.pair()
+    }
+}
+
+impl<'j> From<&'j Cookie> for Match<'j> {
+    fn from(cookie: &'j Cookie) -> Match<'j> {
+        Match::from(Cow::Borrowed(cookie))
+    }
+}
+
+impl<'j> From<Cow<'j, Cookie>> for Match<'j> {
+    fn from(cookie: Cow<'j, Cookie>) -> Match<'j> {
+        Match { cookie }
+    }
+}
+
+impl<'j> Deref for Match<'j> {
+    type Target = Attributes;
+
+    fn deref(&self) -> &Attributes {
+        &self.cookie
+    }
+}
+
+/// A callback notified of changes to a jar.
+type Observer = Box<dyn FnMut(&Event) + Send>;
+
+/// The callbacks to notify of changes to a jar.
+#[derive(Default)]
+struct Observers(Vec<Observer>);
+
+impl Observers {
+    /// Notify all of the callbacks of a change.
+    fn notify(&mut self, event: Event) {
+        for observer in &mut self.0 {
+            observer(&event);
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
+
 /// A jar containing the cookies seen so far.
+///
+/// Cookies with the `Partitioned` attribute are stored separately for each top-level site they
+/// were set from and are only matched for requests made from that same top-level site.
 #[derive(Debug)]
-pub struct Jar<T: Clock> {
+pub struct Jar<T: Clock, S: CookieStore = TreeStore> {
     clock: T,
-    domain: Domain,
-    hosts: HashMap<IpAddr, Path>,
+    store: S,
+    observers: Observers,
+    policy: Policies,
+    max_cookies: Option<usize>,
 }
 
 impl Default for Jar<ClockFn> {
     fn default() -> Jar<ClockFn> {
-        Jar {
-            clock: now_utc,
-            domain: Default::default(),
-            hosts: Default::default(),
-        }
+        Jar::with_clock(SystemTime::now)
     }
 }
 
@@ -62,162 +167,629 @@ impl<T: Clock> Jar<T> {
 
     /// Create a jar with a specific time source.
     pub fn with_clock(clock: T) -> Jar<T> {
+        Jar::with_store(clock, Default::default())
+    }
+}
+
+impl<T: Clock, S: CookieStore> Jar<T, S> {
+    /// Create a jar with a specific time source and cookie store.
+    pub fn with_store(clock: T, store: S) -> Jar<T, S> {
         Jar {
-            clock: clock,
-            domain: Default::default(),
-            hosts: Default::default(),
+            clock,
+            store,
+            observers: Default::default(),
+            policy: Default::default(),
+            max_cookies: None,
         }
     }
 
-    /// Add a cookie to the jar.
-    pub fn add_cookie(&mut self, cookie: Cookie) {
-        let (host, path, attributes) = cookie.explode();
-        let path_segments = path.trim_left_matches('/').split('/');
-        match host {
-            Host::Domain(domain) => {
-                let domain_segments: Vec<_> = domain.trim_matches('.').split('.').collect();
-                self.domain.add_cookie(domain_segments, path_segments, attributes);
-            }
-            Host::Ipv4(addr) => {
-                self.update_host(IpAddr::V4(addr), path_segments, attributes);
-            }
-            Host::Ipv6(addr) => {
-                self.update_host(IpAddr::V6(addr), path_segments, attributes);
-            }
+    /// Limit the number of cookies held by the jar.
+    ///
+    /// When the limit is exceeded, expired cookies are removed first and then cookies are evicted
+    /// in order of their last access time. The jar does not update that time when a cookie is
+    /// sent, so it is the time the cookie was last stored unless one was given, as it is for
+    /// cookies imported from a browser.
+    pub fn max_cookies(self, max_cookies: usize) -> Jar<T, S> {
+        Jar {
+            max_cookies: Some(max_cookies),
+            ..
+            self
+        }
+    }
+
+    /// Decide which cookies the jar stores and sends with a policy.
+    ///
+    /// By default all cookies are accepted.
+    pub fn policy<P: Policy + Send + 'static>(self, policy: P) -> Jar<T, S> {
+        Jar {
+            policy: Policies::new().with(policy),
+            ..
+            self
         }
     }
 
-    /// Update a cookie for a host.
-    fn update_host<'s, S>(&mut self, host: IpAddr, segments: S, attributes: Attributes)
+    /// Call a function with every subsequent change to the cookies held by the jar.
+    pub fn observe<F>(&mut self, observer: F)
     where
-        S: Iterator<Item = &'s str> + 's,
+        F: FnMut(&Event) + Send + 'static,
     {
-        self.hosts.entry(host)
-            .or_insert_with(Path::default)
-            .add_cookie(segments, attributes);
+        self.observers.0.push(Box::new(observer));
     }
 
-    /// Get the matching cookies for a Url.
-    pub fn url_matches<'j>(&'j self, url: &'j Url) -> impl Iterator<Item = &'j Pair> {
-        let path_segments = url_dir_path(url).trim_left_matches('/').split('/');
-        match url.host() {
-            Some(Host::Domain(domain)) => {
-                let domain_segments: Vec<_> = domain.trim_matches('.').split('.').collect();
-                self.domain.match_url(domain_segments, path_segments)
+    /// Get the store holding the cookies of the jar.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Add a cookie to the jar.
+    ///
+    /// The top-level site is the URL of the page being viewed when the cookie was received.
+    /// Partitioned cookies received without a top-level site are stored unpartitioned.
+    ///
+    /// A cookie that has already expired is not stored and instead removes any cookie with the
+    /// same host, path and name.
+    ///
+    /// The policy of the jar is consulted with the top-level site as the first-party context and
+    /// may reject the cookie or shorten its lifetime. The lifetime of a cookie that has already
+    /// expired is left alone so that it still removes the cookie it replaces.
+    ///
+    /// Returns whether the cookie was stored.
+    pub fn add_cookie(&mut self, mut cookie: Cookie, top_level_site: Option<&Url>) -> bool {
+        let context = Context::new(borrow_host(cookie.host()), top_level_site);
+        let decision = self.policy.store(&cookie, &context);
+        let now = self.now();
+        cookie.set_stored_time(now);
+        match decision {
+            Decision::Reject => return false,
+            _ if cookie.expired_since(now) => {}
+            Decision::Accept => {}
+            Decision::Session => cookie.make_session(),
+            Decision::Limit(limit) => cookie.limit_lifetime(now, limit),
+        }
+
+        let key = if cookie.partitioned() {
+            partition_key(top_level_site)
+        } else {
+            None
+        };
+
+        self.add_to_partition(cookie, key)
+    }
+
+    /// Add a cookie to the partition with a given key and return whether it was stored.
+    pub(crate) fn add_to_partition(&mut self, mut cookie: Cookie, key: Option<Host>) -> bool {
+        let now = self.now();
+        cookie.set_stored_time(now);
+        let old = self.store.remove(
+            cookie.host(),
+            cookie.path(),
+            cookie.pair().name(),
+            key.as_ref(),
+        );
+
+        if cookie.expired_since(now) {
+            if let Some(ref old) = old {
+                self.observers.notify(Event::Expired(old));
             }
-            Some(Host::Ipv4(addr)) => self.host_matches(IpAddr::V4(addr), path_segments),
-            Some(Host::Ipv6(addr)) => self.host_matches(IpAddr::V6(addr), path_segments),
-            _ => Box::new(iter::empty()),
+            return false;
         }
+
+        // A replacement keeps the creation time of the cookie it replaces.
+        let creation_time = old.as_ref()
+            .and_then(|old| old.creation_time())
+            .or_else(|| cookie.creation_time())
+            .unwrap_or(now);
+        cookie.set_creation_time(creation_time);
+        if cookie.last_access_time().is_none() {
+            cookie.set_last_access_time(now);
+        }
+
+        match old {
+            Some(ref old) => self.observers.notify(Event::Replaced {
+                old,
+                new: &cookie,
+            }),
+            None => self.observers.notify(Event::Inserted(&cookie)),
+        }
+
+        self.store.insert(cookie, key);
+        self.evict_excess();
+        true
     }
 
-    /// Get all of the matches for a specific host.
-    fn host_matches<'j, 's, S>(&'j self, host: IpAddr, segments: S)
-        -> Box<Iterator<Item = &'j Pair> + 'j>
-    where
-        S: Iterator<Item = &'s str> + 's,
-    {
-        if let Some(host) = self.hosts.get(&host) {
-            host.match_url(segments, HostMatch::Exact)
-        } else {
-            Box::new(iter::empty())
+    /// Remove a cookie from the jar by its host, path and name.
+    ///
+    /// Partitioned cookies are removed by giving the top-level site they were stored for while
+    /// unpartitioned cookies are removed by giving no top-level site.
+    pub fn remove_cookie(
+        &mut self,
+        host: &Host,
+        path: &str,
+        name: &str,
+        top_level_site: Option<&Url>,
+    ) -> Option<Cookie> {
+        let key = partition_key(top_level_site);
+        let removed = self.store.remove(host, path, name, key.as_ref());
+        if let Some(ref removed) = removed {
+            self.observers.notify(Event::Removed(removed));
         }
+        removed
     }
-}
 
-/// The given URL is an exact host match.
-#[derive(PartialEq, Eq, Clone, Copy)]
-enum HostMatch {
-    Exact,
-    Suffix,
-}
+    /// Remove all of the cookies that have expired.
+    pub fn purge_expired(&mut self) -> Vec<Cookie> {
+        let now = self.now();
+        let purged = self.store.purge(now);
+        for cookie in &purged {
+            self.observers.notify(Event::Expired(cookie));
+        }
+        purged
+    }
 
-/// The heirarchy of domains.
-#[derive(Debug, Default)]
-struct Domain {
-    path: Path,
-    children: HashMap<String, Domain>,
-}
+    /// Evict the cookies with the oldest last access time while the jar holds more than its limit.
+    fn evict_excess(&mut self) {
+        let max_cookies = match self.max_cookies {
+            Some(max_cookies) => max_cookies,
+            None => return,
+        };
 
-impl Domain {
-    /// Add a set of cookie attributes to a domain.
-    pub fn add_cookie<'p, P>(&mut self, mut segments: Vec<&str>, path: P, attributes: Attributes)
-    where
-        P: Iterator<Item = &'p str> + 'p,
-    {
-        if let Some(child) = segments.pop() {
-            self.children.entry(child.to_owned())
-                .or_insert_with(Domain::default)
-                .add_cookie(segments, path, attributes);
-        } else {
-            self.path.add_cookie(path, attributes);
+        let mut count = self.store.iter().count();
+        if count > max_cookies {
+            count -= self.purge_expired().len();
         }
-    }
 
-    /// Get all of the attributes that match a given request URL.
-    pub fn match_url<'c, 'p, P>(&'c self, mut segments: Vec<&str>, path: P)
-        -> Box<Iterator<Item = &'c Pair> + 'c>
-    where
-        P: Iterator<Item = &'p str> + 'p + Clone,
-    {
+        while count > max_cookies {
+            let oldest = self.store
+                .iter()
+                .min_by_key(|(_, cookie)| {
+                    (cookie.last_access_time(), cookie.creation_time())
+                })
+                .map(|(key, cookie)| {
+                    let name = cookie.pair().name().to_owned();
+                    let key = key.map(Cow::into_owned);
+                    (key, cookie.host().clone(), cookie.path().to_owned(), name)
+                });
 
-        if let Some(child) = segments.pop() {
-            let iter = self.path.match_url(path.clone(), HostMatch::Suffix);
-            if let Some(child) = self.children.get(child) {
-                Box::new(iter.chain(child.match_url(segments, path)))
-            } else {
-                Box::new(iter)
+            let evicted = oldest.and_then(|(key, host, path, name)| {
+                self.store.remove(&host, &path, &name, key.as_ref())
+            });
+
+            match evicted {
+                Some(ref evicted) => self.observers.notify(Event::Evicted(evicted)),
+                None => break,
             }
-        } else {
-            Box::new(self.path.match_url(path, HostMatch::Exact))
+            count -= 1;
         }
     }
+
+    /// Get the matching cookies for a Url.
+    ///
+    /// The top-level site is the URL of the page being viewed when the request is made.
+    /// Partitioned cookies are only matched if they were stored for the same top-level site.
+    ///
+    /// Following [RFC6265 section 5.4](https://tools.ietf.org/html/rfc6265#section-5.4), cookies
+    /// that have expired are not matched and secure cookies are only matched for URLs with a
+    /// secure scheme. Cookies rejected by the policy of the jar for the request are not matched.
+    ///
+    /// Matching borrows the cookies from the store of the jar and does not allocate.
+    pub fn url_matches<'j>(&'j self, url: &'j Url, top_level_site: Option<&'j Url>)
+        -> impl Iterator<Item = Match<'j>>
+    {
+        // The host is only needed to give a policy the context of the request.
+        let host = if self.policy.is_empty() {
+            None
+        } else {
+            url.host()
+        };
+        let partitioned = top_level_site
+            .and_then(Url::host)
+            .map(|site| self.store.matches(url, Some(registrable(site))));
+
+        let now = self.now();
+        let secure = is_secure_scheme(url.scheme());
+
+        self.store
+            .matches(url, None)
+            .chain(partitioned.into_iter().flatten())
+            .filter(move |cookie| !cookie.expired_since(now) && (secure || !cookie.secure()))
+            .filter(move |cookie| match host {
+                Some(ref host) => {
+                    let context = Context::new(host.clone(), top_level_site);
+                    self.policy.send(cookie, &context) != Decision::Reject
+                }
+                None => true,
+            })
+            .map(Match::from)
+    }
+
+    /// Get the value of the `Cookie` header for a request to a URL.
+    ///
+    /// Cookies with longer paths are listed first and cookies with paths of the same length are
+    /// listed in the order they were created, as in
+    /// [RFC6265 section 5.4](https://tools.ietf.org/html/rfc6265#section-5.4).
+    ///
+    /// None if no cookies match the URL.
+    pub fn cookie_header(&self, url: &Url, top_level_site: Option<&Url>) -> Option<String> {
+        format_cookie_header(self.url_matches(url, top_level_site))
+    }
+
+    /// Get all of the cookies in the jar along with the key of the partition they are in.
+    pub(crate) fn partitioned_cookies<'j>(&'j self) -> StoredCookies<'j> {
+        self.store.iter()
+    }
+
+    /// Get the current time from the clock of the jar.
+    pub(crate) fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+}
+
+/// Join matching cookies into the value of a `Cookie` header.
+///
+/// None if there are no cookies.
+pub(crate) fn format_cookie_header<'j, I>(matches: I) -> Option<String>
+where
+    I: Iterator<Item = Match<'j>>,
+{
+    let mut cookies: Vec<_> = matches.collect();
+    if cookies.is_empty() {
+        return None;
+    }
+
+    cookies.sort_by(|a, b| {
+        b.path().len()
+            .cmp(&a.path().len())
+            .then_with(|| a.creation_time().cmp(&b.creation_time()))
+    });
+
+    let pairs: Vec<_> = cookies.iter().map(|cookie| cookie.pair().as_str()).collect();
+    Some(pairs.join("; "))
 }
 
-/// The heriarchy of paths.
-#[derive(Debug, Default)]
-struct Path {
-    cookies: HashMap<String, Attributes>,
-    children: HashMap<String, Path>,
+/// Check if a URL scheme is for a secure connection.
+fn is_secure_scheme(scheme: &str) -> bool {
+    scheme == "https" || scheme == "wss"
 }
 
-impl Path {
-    /// Add a cookie to the matching path.
-    pub fn add_cookie<'s, S>(&mut self, mut segments: S, attributes: Attributes)
-    where
-        S: Iterator<Item = &'s str> + 's,
-    {
-        if let Some(child) = segments.next() {
-            self.children
-                .entry(child.to_owned())
-                .or_insert_with(Path::default)
-                .add_cookie(segments, attributes);
-        } else {
-            self.cookies.insert(attributes.pair().name().to_owned(), attributes);
+/// Get the key of the partition for a top-level site.
+fn partition_key(top_level_site: Option<&Url>) -> Option<Host> {
+    top_level_site
+        .and_then(Site::from_url)
+        .map(Site::into_host)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::{Arc, Mutex};
+    use datetime::from_unix_seconds;
+
+    thread_local! {
+        /// The current time in seconds used by the test jars.
+        static NOW: Cell<i64> = const { Cell::new(1_000_000) };
+    }
+
+    /// The current time used by the test jars.
+    fn now() -> SystemTime {
+        NOW.with(|now| from_unix_seconds(now.get()))
+    }
+
+    /// Move the current time of the test jars forward.
+    fn advance(seconds: i64) {
+        NOW.with(|now| now.set(now.get() + seconds));
+    }
+
+    /// A jar whose events are recorded as strings.
+    fn observed_jar() -> (Jar<ClockFn>, Arc<Mutex<Vec<String>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut jar = Jar::with_clock(now as ClockFn);
+
+        let recorded = events.clone();
+        jar.observe(move |event| {
+            let event = match *event {
+                Event::Inserted(cookie) => format!("inserted {}", cookie.pair().as_str()),
+                Event::Replaced { old, new } => {
+                    format!("replaced {} {}", old.pair().as_str(), new.pair().as_str())
+                }
+                Event::Expired(cookie) => format!("expired {}", cookie.pair().as_str()),
+                Event::Evicted(cookie) => format!("evicted {}", cookie.pair().as_str()),
+                Event::Removed(cookie) => format!("removed {}", cookie.pair().as_str()),
+            };
+            recorded.lock().unwrap().push(event);
+        });
+
+        (jar, events)
+    }
+
+    /// Parse a cookie from the root of a test origin.
+    fn parse(cookie: &str) -> Cookie {
+        let origin = "https://www.example.com/".parse().unwrap();
+        Cookie::parse(cookie, &origin).expect("Could not parse cookie")
+    }
+
+    #[test]
+    fn change_events() {
+        let (mut jar, events) = observed_jar();
+        let host = Host::parse("www.example.com").unwrap();
+
+        jar.add_cookie(parse("a=1; Max-Age=60"), None);
+        jar.add_cookie(parse("b=1; Max-Age=600"), None);
+        advance(10);
+        jar.add_cookie(parse("a=2; Max-Age=60"), None);
+        jar.add_cookie(parse("b=2; Max-Age=0"), None);
+        jar.add_cookie(parse("c=1; Max-Age=0"), None);
+        jar.add_cookie(parse("d=1; Max-Age=5"), None);
+        assert_eq!(jar.remove_cookie(&host, "/", "missing", None), None);
+        assert!(jar.remove_cookie(&host, "/", "a", None).is_some());
+        advance(10);
+        assert_eq!(jar.purge_expired().len(), 1);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "inserted a=1",
+                "inserted b=1",
+                "replaced a=1 a=2",
+                "expired b=1",
+                "inserted d=1",
+                "removed a=2",
+                "expired d=1",
+            ]
+        );
+    }
+
+    #[test]
+    fn replacement_keeps_creation_time() {
+        let (mut jar, _) = observed_jar();
+
+        jar.add_cookie(parse("a=1"), None);
+        let created = now();
+        advance(10);
+        jar.add_cookie(parse("a=2"), None);
+
+        let (_, cookie) = jar.partitioned_cookies().next().unwrap();
+        assert_eq!(cookie.pair().as_str(), "a=2");
+        assert_eq!(cookie.creation_time(), Some(created));
+        assert_eq!(cookie.last_access_time(), Some(now()));
+    }
+
+    #[test]
+    fn evict_least_recently_stored() {
+        let (jar, events) = observed_jar();
+        let mut jar = jar.max_cookies(2);
+
+        jar.add_cookie(parse("a=1"), None);
+        advance(1);
+        jar.add_cookie(parse("b=1; Max-Age=5"), None);
+        advance(1);
+        jar.add_cookie(parse("c=1"), None);
+        advance(10);
+        jar.add_cookie(parse("d=1"), None);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "inserted a=1",
+                "inserted b=1",
+                "inserted c=1",
+                "evicted a=1",
+                "inserted d=1",
+                "expired b=1",
+            ]
+        );
+        assert_eq!(matching_names(&jar, "https://www.example.com/", None), ["c", "d"]);
+    }
+
+    #[test]
+    fn add_reports_stored() {
+        use policy::BlockThirdParty;
+
+        let mut jar = Jar::with_clock(now as ClockFn).policy(BlockThirdParty);
+        let site = "https://www.example.com/".parse().unwrap();
+        let other_site = "https://other.example/".parse().unwrap();
+
+        assert!(jar.add_cookie(parse("a=1"), Some(&site)));
+        assert!(!jar.add_cookie(parse("a=; Max-Age=0"), Some(&site)));
+        assert!(!jar.add_cookie(parse("b=1"), Some(&other_site)));
+        assert!(matching_names(&jar, "https://www.example.com/", None).is_empty());
+    }
+
+    #[test]
+    fn policy_decisions() {
+        use policy::*;
+        use cookie::Expires;
+        use std::time::Duration;
+
+        let policies = Policies::new()
+            .with(BlockThirdParty)
+            .with(SessionDomains(Domains::parse(vec!["session.example"]).unwrap()))
+            .with(MaxLifetime(Duration::from_secs(7 * 24 * 60 * 60)));
+        let mut jar = Jar::with_clock(now as ClockFn).policy(policies);
+
+        let first_party = "https://www.example.com/".parse().unwrap();
+        let other_site = "https://other.example/".parse().unwrap();
+        jar.add_cookie(parse("long=1; Max-Age=31536000"), Some(&first_party));
+        jar.add_cookie(parse("short=1; Max-Age=60"), Some(&first_party));
+        jar.add_cookie(parse("tracker=1"), Some(&other_site));
+
+        let session = "https://session.example/".parse().unwrap();
+        let cookie = Cookie::parse("a=1; Max-Age=60", &session).unwrap();
+        jar.add_cookie(cookie, Some(&session));
+
+        let expiry = |name: &str| {
+            jar.partitioned_cookies()
+                .find(|(_, cookie)| cookie.pair().name() == name)
+                .map(|(_, cookie)| *cookie.expiry())
+        };
+        let week = from_unix_seconds(NOW.with(Cell::get) + 7 * 24 * 60 * 60);
+        assert_eq!(expiry("long"), Some(Expires::AtUtc(week)));
+        assert_eq!(expiry("short"), Some(Expires::AtUtc(now() + Duration::from_secs(60))));
+        assert_eq!(expiry("tracker"), None);
+        assert_eq!(expiry("a"), Some(Expires::Never));
+
+        let site = "https://www.example.com/";
+        assert_eq!(matching_names(&jar, site, Some(site)), ["long", "short"]);
+        assert_eq!(
+            matching_names(&jar, site, Some("https://login.example.com/")),
+            ["long", "short"]
+        );
+        assert!(matching_names(&jar, site, Some("https://other.example/")).is_empty());
+    }
+
+    #[test]
+    fn policy_keeps_deletions() {
+        use policy::*;
+
+        let policy = SessionDomains(Domains::parse(vec!["example.com"]).unwrap());
+        let mut jar = Jar::with_clock(now as ClockFn).policy(policy);
+        let site = "https://www.example.com/";
+
+        jar.add_cookie(parse("sid=1; Max-Age=3600"), None);
+        assert_eq!(jar.cookie_header(&site.parse().unwrap(), None).as_deref(), Some("sid=1"));
+        jar.add_cookie(parse("sid=; Max-Age=0"), None);
+        assert_eq!(jar.cookie_header(&site.parse().unwrap(), None), None);
+        assert_eq!(jar.partitioned_cookies().count(), 0);
+    }
+
+    #[test]
+    fn limited_lifetime_stops_sending() {
+        use policy::*;
+        use std::time::Duration;
+
+        let policy = MaxLifetime(Duration::from_secs(60));
+        let mut jar = Jar::with_clock(now as ClockFn).policy(policy);
+        let site = "https://www.example.com/";
+
+        jar.add_cookie(parse("l=1; Max-Age=31536000"), None);
+        advance(30);
+        assert_eq!(matching_names(&jar, site, None), ["l"]);
+        advance(970);
+        assert!(matching_names(&jar, site, None).is_empty());
+        assert_eq!(jar.cookie_header(&site.parse().unwrap(), None), None);
+    }
+
+    /// Get the names of all of the cookies matching a URL.
+    fn matching_names(jar: &Jar<ClockFn>, url: &str, top_level_site: Option<&str>) -> Vec<String> {
+        let url = url.parse().unwrap();
+        let top_level_site = top_level_site.map(|site| site.parse().unwrap());
+        let mut names: Vec<_> = jar
+            .url_matches(&url, top_level_site.as_ref())
+            .map(|cookie| cookie.pair().name().to_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn match_metadata() {
+        let mut jar = Jar::with_clock(now as ClockFn);
+        jar.add_cookie(parse("a=1; Domain=example.com; Path=/docs; Secure; HttpOnly"), None);
+
+        let url = "https://www.example.com/docs/index.html".parse().unwrap();
+        let matched: Vec<_> = jar.url_matches(&url, None).collect();
+        assert_eq!(matched.len(), 1);
+
+        let cookie = &matched[0];
+        assert_eq!(cookie.pair().as_str(), "a=1");
+        assert_eq!(cookie.domain(), Some("example.com"));
+        assert_eq!(cookie.path(), "/docs");
+        assert!(!cookie.host_only());
+        assert!(cookie.secure());
+        assert!(cookie.http_only());
+        assert_eq!(cookie.creation_time(), Some(now()));
+    }
+
+    #[test]
+    fn expired_not_matched() {
+        let mut jar = Jar::with_clock(now as ClockFn);
+        jar.add_cookie(parse("a=1; Max-Age=10"), None);
+        jar.add_cookie(parse("b=1"), None);
+
+        let site = "https://www.example.com/";
+        assert_eq!(matching_names(&jar, site, None), ["a", "b"]);
+        advance(100);
+        assert_eq!(matching_names(&jar, site, None), ["b"]);
+        assert_eq!(jar.cookie_header(&site.parse().unwrap(), None).as_deref(), Some("b=1"));
+    }
+
+    #[test]
+    fn secure_only_for_secure_schemes() {
+        let mut jar = Jar::with_clock(now as ClockFn);
+        jar.add_cookie(parse("s=1; Secure"), None);
+        jar.add_cookie(parse("p=1"), None);
+
+        assert_eq!(matching_names(&jar, "https://www.example.com/", None), ["p", "s"]);
+        assert_eq!(matching_names(&jar, "wss://www.example.com/", None), ["p", "s"]);
+        assert_eq!(matching_names(&jar, "http://www.example.com/", None), ["p"]);
+        assert_eq!(matching_names(&jar, "ws://www.example.com/", None), ["p"]);
+    }
+
+    #[test]
+    fn path_matches() {
+        let origin = "https://www.example.com/".parse().unwrap();
+
+        let mut jar = Jar::default();
+        let cookies = [
+            "root=1; Path=/",
+            "account=1; Path=/account",
+            "settings=1; Path=/account/settings/",
+        ];
+
+        for cookie in cookies.iter() {
+            let cookie = Cookie::parse(cookie, &origin).expect("Could not parse cookie");
+            jar.add_cookie(cookie, None);
         }
+
+        let site = "https://www.example.com";
+        assert_eq!(matching_names(&jar, site, None), ["root"]);
+        assert_eq!(
+            matching_names(&jar, &format!("{}/account", site), None),
+            ["account", "root"]
+        );
+        assert_eq!(
+            matching_names(&jar, &format!("{}/account/settings/page.html", site), None),
+            ["account", "root", "settings"]
+        );
+        assert_eq!(matching_names(&jar, &format!("{}/other/", site), None), ["root"]);
     }
 
-    /// Get all of the attributes that match a given request URL.
-    pub fn match_url<'c, 's, S>(&'c self, mut segments: S, host: HostMatch)
-        -> Box<Iterator<Item = &'c Pair> + 'c>
-    where
-        S: Iterator<Item = &'s str> + 's,
-    {
-        let iter = self.cookies.values()
-            .filter(move |attributes| match host {
-                HostMatch::Exact => true,
-                HostMatch::Suffix => !attributes.host_only(),
-            })
-            .map(Attributes::pair);
+    #[test]
+    fn partitioned_matches() {
+        let origin = "https://widget.example/".parse().unwrap();
+        let first_site = "https://first.example/".parse().unwrap();
+        let second_site = "https://second.example/".parse().unwrap();
 
-        if let Some(child) = segments.next() {
-            if let Some(child) = self.children.get(child) {
-                Box::new(iter.chain(child.match_url(segments, host)))
-            } else {
-                Box::new(iter)
-            }
-        } else {
-            Box::new(iter)
+        let mut jar = Jar::default();
+        let cookies = [
+            ("shared=1; Path=/; Secure", Some(&first_site)),
+            ("first=1; Path=/; Secure; Partitioned", Some(&first_site)),
+            ("second=1; Path=/; Secure; Partitioned", Some(&second_site)),
+            ("unkeyed=1; Path=/; Secure; Partitioned", None),
+        ];
+
+        for &(cookie, top_level_site) in cookies.iter() {
+            let cookie = Cookie::parse(cookie, &origin).expect("Could not parse cookie");
+            jar.add_cookie(cookie, top_level_site);
         }
+
+        let widget = "https://widget.example/frame";
+        assert_eq!(matching_names(&jar, widget, None), ["shared", "unkeyed"]);
+        assert_eq!(
+            matching_names(&jar, widget, Some("https://first.example/page")),
+            ["first", "shared", "unkeyed"]
+        );
+        assert_eq!(
+            matching_names(&jar, widget, Some("https://www.first.example/")),
+            ["first", "shared", "unkeyed"]
+        );
+        assert_eq!(
+            matching_names(&jar, widget, Some("https://second.example/")),
+            ["second", "shared", "unkeyed"]
+        );
+        assert_eq!(
+            matching_names(&jar, widget, Some("https://third.example/")),
+            ["shared", "unkeyed"]
+        );
     }
 }
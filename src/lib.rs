@@ -5,18 +5,73 @@
 #![deny(missing_docs)]
 #![deny(missing_debug_implementations)]
 
+#[cfg(feature = "private")]
+extern crate aes_gcm;
+#[cfg(any(feature = "signed", feature = "private"))]
+extern crate base64;
+#[cfg(feature = "chrono")]
+extern crate chrono;
 #[macro_use]
 extern crate error_chain;
+#[cfg(feature = "http")]
+extern crate http;
+#[cfg(feature = "signed")]
+extern crate hmac;
 extern crate idna;
 #[cfg(test)]
 #[macro_use]
 extern crate pretty_assertions;
+#[cfg(test)]
+extern crate proptest;
+extern crate psl;
+#[cfg(feature = "reqwest")]
+extern crate reqwest;
+#[cfg(any(feature = "firefox", feature = "chromium"))]
+extern crate rusqlite;
+#[cfg(feature = "signed")]
+extern crate sha2;
 extern crate time;
+#[cfg(feature = "time03")]
+extern crate time03;
+#[cfg(all(test, feature = "tower"))]
+extern crate tokio;
+#[cfg(all(test, feature = "tower"))]
+extern crate tower;
+#[cfg(feature = "tower")]
+extern crate tower_layer;
+#[cfg(feature = "tower")]
+extern crate tower_service;
 extern crate url;
+#[cfg(feature = "reqwest")]
+extern crate url2;
 
 mod cookie;
+mod datetime;
 pub mod error;
+#[cfg(fuzzing)]
+#[doc(hidden)]
+pub mod fuzz;
+#[cfg(feature = "http")]
+mod headers;
+#[cfg(any(feature = "firefox", feature = "chromium"))]
+pub mod import;
 mod jar;
+#[cfg(feature = "tower")]
+pub mod middleware;
+pub mod persist;
+pub mod policy;
+#[cfg(feature = "private")]
+pub mod private;
+pub mod proxy;
+pub mod redirect;
+pub mod server;
+pub mod shared;
+#[cfg(feature = "signed")]
+pub mod signed;
+pub mod site;
+pub mod store;
 
 pub use cookie::*;
-pub use jar::{Jar, Clock};
+#[cfg(feature = "time03")]
+pub use datetime::OffsetDateTimeFn;
+pub use jar::{Jar, Clock, ClockFn, Event, Match};
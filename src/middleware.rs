@@ -0,0 +1,628 @@
+//! Middleware applying a jar to the requests of a `tower` client service.
+//!
+//! A [`CookieLayer`](struct.CookieLayer.html) wraps a client service so that each request is
+//! sent with the `Cookie` header for its URI and the `Set-Cookie` headers of each response are
+//! stored in a [`SharedJar`](../shared/struct.SharedJar.html).
+//!
+//! Redirects are followed by the layer returned from
+//! [`CookieLayer::follow_redirects`](struct.CookieLayer.html#method.follow_redirects), which
+//! sends every hop through the jar so that the cookies set by one hop are stored and sent with
+//! the next. The jar only sees the final response of a wrapped service that follows redirects
+//! itself; such a service should insert a [`ResponseUri`](struct.ResponseUri.html) into the
+//! extensions of that response so its cookies are at least stored for the URI it was received
+//! from.
+//!
+//! Cookies are stored and matched without a top-level site. Requests with a relative URI are
+//! passed through unchanged.
+
+use std::fmt;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION};
+use http::header::PROXY_AUTHORIZATION;
+use http::{HeaderMap, Method, Request, Response, StatusCode, Uri, Version};
+use tower_layer::Layer;
+use tower_service::Service;
+use url::Url;
+
+use ::headers::url_from_uri;
+use ::jar::Clock;
+use ::shared::SharedJar;
+use ::store::{CookieStore, TreeStore};
+
+/// The URI a response was received from when it differs from that of the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseUri(pub Uri);
+
+/// A layer that applies a jar to the requests of a service.
+pub struct CookieLayer<T: Clock, S: CookieStore = TreeStore> {
+    jar: Arc<SharedJar<T, S>>,
+}
+
+impl<T: Clock, S: CookieStore> CookieLayer<T, S> {
+    /// Apply a jar to the requests of the services wrapped by the layer.
+    pub fn new(jar: Arc<SharedJar<T, S>>) -> CookieLayer<T, S> {
+        CookieLayer { jar }
+    }
+
+    /// Follow up to a number of redirects, storing the cookies set by every hop.
+    pub fn follow_redirects(self, limit: usize) -> RedirectLayer<T, S> {
+        RedirectLayer {
+            jar: self.jar,
+            limit,
+        }
+    }
+}
+
+impl<T: Clock, S: CookieStore> Clone for CookieLayer<T, S> {
+    fn clone(&self) -> CookieLayer<T, S> {
+        CookieLayer::new(self.jar.clone())
+    }
+}
+
+impl<T: Clock, S: CookieStore> fmt::Debug for CookieLayer<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CookieLayer")
+    }
+}
+
+impl<I, T: Clock, S: CookieStore> Layer<I> for CookieLayer<T, S> {
+    type Service = CookieService<I, T, S>;
+
+    fn layer(&self, inner: I) -> CookieService<I, T, S> {
+        CookieService {
+            inner,
+            jar: self.jar.clone(),
+        }
+    }
+}
+
+/// A service that sends and stores the cookies in a jar for the requests of a wrapped service.
+pub struct CookieService<I, T: Clock, S: CookieStore = TreeStore> {
+    inner: I,
+    jar: Arc<SharedJar<T, S>>,
+}
+
+impl<I, T: Clock, S: CookieStore> CookieService<I, T, S> {
+    /// Apply a jar to the requests of a service.
+    pub fn new(inner: I, jar: Arc<SharedJar<T, S>>) -> CookieService<I, T, S> {
+        CookieService {
+            inner,
+            jar,
+        }
+    }
+
+    /// Get the wrapped service.
+    pub fn get_ref(&self) -> &I {
+        &self.inner
+    }
+
+    /// Unwrap the wrapped service.
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<I: Clone, T: Clock, S: CookieStore> Clone for CookieService<I, T, S> {
+    fn clone(&self) -> CookieService<I, T, S> {
+        CookieService::new(self.inner.clone(), self.jar.clone())
+    }
+}
+
+impl<I: fmt::Debug, T: Clock, S: CookieStore> fmt::Debug for CookieService<I, T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CookieService").field("inner", &self.inner).finish()
+    }
+}
+
+impl<I, B, R, T: Clock, S: CookieStore> Service<Request<B>> for CookieService<I, T, S>
+where
+    I: Service<Request<B>, Response = Response<R>>,
+{
+    type Response = Response<R>;
+    type Error = I::Error;
+    type Future = ResponseFuture<I::Future, T, S>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), I::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        let url = url_from_uri(request.uri()).ok();
+        if let Some(ref url) = url {
+            // A header that cannot be written leaves the request without cookies from the jar.
+            let _ = self.jar.lock().write_headers(url, request.headers_mut(), None);
+        }
+
+        ResponseFuture {
+            inner: Box::pin(self.inner.call(request)),
+            jar: self.jar.clone(),
+            url,
+        }
+    }
+}
+
+/// The response of a [`CookieService`](struct.CookieService.html).
+///
+/// The cookies set by the response are stored once it is received.
+pub struct ResponseFuture<F, T: Clock, S: CookieStore = TreeStore> {
+    inner: Pin<Box<F>>,
+    jar: Arc<SharedJar<T, S>>,
+    url: Option<Url>,
+}
+
+impl<F, T: Clock, S: CookieStore> ResponseFuture<F, T, S> {
+    /// Store the cookies set by a response.
+    fn store<R>(&self, response: &Response<R>) {
+        let received = response
+            .extensions()
+            .get::<ResponseUri>()
+            .and_then(|uri| url_from_uri(&uri.0).ok());
+
+        if let Some(url) = received.as_ref().or(self.url.as_ref()) {
+            self.jar.lock().store_headers(url, response.headers(), None);
+        }
+    }
+}
+
+impl<F, T: Clock, S: CookieStore> fmt::Debug for ResponseFuture<F, T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ResponseFuture").field("url", &self.url).finish()
+    }
+}
+
+impl<F, R, E, T: Clock, S: CookieStore> Future for ResponseFuture<F, T, S>
+where
+    F: Future<Output = Result<Response<R>, E>>,
+{
+    type Output = Result<Response<R>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // The wrapped future is boxed so the response future itself never needs to be pinned.
+        let this = Pin::into_inner(self);
+        let response = match this.inner.as_mut().poll(cx) {
+            Poll::Ready(Ok(response)) => response,
+            other => return other,
+        };
+
+        this.store(&response);
+        Poll::Ready(Ok(response))
+    }
+}
+
+/// A layer that applies a jar to the requests of a service and follows their redirects.
+pub struct RedirectLayer<T: Clock, S: CookieStore = TreeStore> {
+    jar: Arc<SharedJar<T, S>>,
+    limit: usize,
+}
+
+impl<T: Clock, S: CookieStore> Clone for RedirectLayer<T, S> {
+    fn clone(&self) -> RedirectLayer<T, S> {
+        RedirectLayer {
+            jar: self.jar.clone(),
+            limit: self.limit,
+        }
+    }
+}
+
+impl<T: Clock, S: CookieStore> fmt::Debug for RedirectLayer<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RedirectLayer").field("limit", &self.limit).finish()
+    }
+}
+
+impl<I, T: Clock, S: CookieStore> Layer<I> for RedirectLayer<T, S> {
+    type Service = RedirectService<I, T, S>;
+
+    fn layer(&self, inner: I) -> RedirectService<I, T, S> {
+        RedirectService {
+            inner: CookieService::new(inner, self.jar.clone()),
+            limit: self.limit,
+        }
+    }
+}
+
+/// A service that follows redirects, sending every hop through a jar.
+///
+/// Responses with a `301`, `302`, `303`, `307` or `308` status and a `Location` header are
+/// followed until the limit is reached. As in a browser, `303` responses and `301` or `302`
+/// responses to a `POST` turn the next request into a `GET` without a body. Credentials given
+/// with the original request are not sent to other origins.
+pub struct RedirectService<I, T: Clock, S: CookieStore = TreeStore> {
+    inner: CookieService<I, T, S>,
+    limit: usize,
+}
+
+impl<I, T: Clock, S: CookieStore> RedirectService<I, T, S> {
+    /// Get the wrapped service.
+    pub fn get_ref(&self) -> &I {
+        self.inner.get_ref()
+    }
+
+    /// Unwrap the wrapped service.
+    pub fn into_inner(self) -> I {
+        self.inner.into_inner()
+    }
+}
+
+impl<I: Clone, T: Clock, S: CookieStore> Clone for RedirectService<I, T, S> {
+    fn clone(&self) -> RedirectService<I, T, S> {
+        RedirectService {
+            inner: self.inner.clone(),
+            limit: self.limit,
+        }
+    }
+}
+
+impl<I: fmt::Debug, T: Clock, S: CookieStore> fmt::Debug for RedirectService<I, T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RedirectService")
+            .field("inner", self.get_ref())
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+impl<I, B, R, T: Clock, S: CookieStore> Service<Request<B>> for RedirectService<I, T, S>
+where
+    I: Service<Request<B>, Response = Response<R>> + Clone,
+    B: Clone + Default,
+{
+    type Response = Response<R>;
+    type Error = I::Error;
+    type Future = RedirectFuture<I, B, T, S>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), I::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let hop = Hop {
+            method: request.method().clone(),
+            uri: request.uri().clone(),
+            version: request.version(),
+            headers: request.headers().clone(),
+            body: request.body().clone(),
+        };
+
+        // The service that was polled ready sends the first hop and a clone takes its place.
+        let clone = self.inner.clone();
+        let mut service = mem::replace(&mut self.inner, clone);
+        let future = service.call(request);
+
+        RedirectFuture {
+            service,
+            future: Some(future),
+            hop,
+            remaining: self.limit,
+        }
+    }
+}
+
+/// The parts of a request needed to send it again to the target of a redirect.
+struct Hop<B> {
+    method: Method,
+    uri: Uri,
+    version: Version,
+    headers: HeaderMap,
+    body: B,
+}
+
+impl<B: Clone + Default> Hop<B> {
+    /// Build the request for this hop.
+    fn request(&self) -> Request<B> {
+        let mut request = Request::new(self.body.clone());
+        *request.method_mut() = self.method.clone();
+        *request.uri_mut() = self.uri.clone();
+        *request.version_mut() = self.version;
+        *request.headers_mut() = self.headers.clone();
+        request
+    }
+
+    /// Move on to the target of a redirect, returning false if the response is not one.
+    fn redirect<R>(&mut self, response: &Response<R>) -> bool {
+        let status = response.status();
+        let change_method = match status {
+            StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND => self.method == Method::POST,
+            StatusCode::SEE_OTHER => self.method != Method::GET && self.method != Method::HEAD,
+            StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => false,
+            _ => return false,
+        };
+
+        let location = match response.headers().get(LOCATION).map(|value| value.to_str()) {
+            Some(Ok(location)) => location,
+            _ => return false,
+        };
+        let (previous, target) = match url_from_uri(&self.uri) {
+            Ok(previous) => match previous.join(location) {
+                Ok(target) => (previous, target),
+                Err(_) => return false,
+            },
+            Err(_) => return false,
+        };
+        let uri = match target.as_str().parse() {
+            Ok(uri) => uri,
+            Err(_) => return false,
+        };
+
+        if change_method {
+            self.method = Method::GET;
+            self.body = B::default();
+            self.headers.remove(CONTENT_LENGTH);
+            self.headers.remove(CONTENT_TYPE);
+        }
+
+        if previous.origin() != target.origin() {
+            self.headers.remove(AUTHORIZATION);
+            self.headers.remove(PROXY_AUTHORIZATION);
+            self.headers.remove(COOKIE);
+        }
+
+        self.uri = uri;
+        true
+    }
+}
+
+/// The response of a [`RedirectService`](struct.RedirectService.html).
+///
+/// The cookies set by each hop are stored before the next hop is sent.
+pub struct RedirectFuture<I, B, T: Clock, S: CookieStore = TreeStore>
+where
+    I: Service<Request<B>>,
+{
+    service: CookieService<I, T, S>,
+    future: Option<ResponseFuture<I::Future, T, S>>,
+    hop: Hop<B>,
+    remaining: usize,
+}
+
+// Neither the wrapped service nor the body are pinned, so the future can be moved between polls.
+impl<I, B, T: Clock, S: CookieStore> Unpin for RedirectFuture<I, B, T, S>
+where
+    I: Service<Request<B>>,
+{
+}
+
+impl<I, B, T: Clock, S: CookieStore> fmt::Debug for RedirectFuture<I, B, T, S>
+where
+    I: Service<Request<B>>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RedirectFuture")
+            .field("uri", &self.hop.uri)
+            .field("remaining", &self.remaining)
+            .finish()
+    }
+}
+
+impl<I, B, R, T: Clock, S: CookieStore> Future for RedirectFuture<I, B, T, S>
+where
+    I: Service<Request<B>, Response = Response<R>>,
+    B: Clone + Default,
+{
+    type Output = Result<Response<R>, I::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = Pin::into_inner(self);
+        loop {
+            let mut future = match this.future.take() {
+                Some(future) => future,
+                None => {
+                    match this.service.poll_ready(cx) {
+                        Poll::Ready(Ok(())) => {}
+                        Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                        Poll::Pending => return Poll::Pending,
+                    }
+                    this.service.call(this.hop.request())
+                }
+            };
+
+            let response = match Pin::new(&mut future).poll(cx) {
+                Poll::Ready(Ok(response)) => response,
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                Poll::Pending => {
+                    this.future = Some(future);
+                    return Poll::Pending;
+                }
+            };
+
+            if this.remaining == 0 || !this.hop.redirect(&response) {
+                return Poll::Ready(Ok(response));
+            }
+            this.remaining -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use http::header::{COOKIE, LOCATION, SET_COOKIE};
+    use http::StatusCode;
+    use jar::{ClockFn, Jar};
+    use std::convert::Infallible;
+    use std::future::{ready, Ready};
+    use std::sync::Mutex;
+    use tokio::runtime::Builder;
+    use tower::{service_fn, ServiceExt};
+
+    /// Send a request through a service and wait for the response.
+    fn send<V>(service: V, request: Request<()>) -> Response<()>
+    where
+        V: Service<Request<()>, Response = Response<()>, Error = Infallible>,
+    {
+        let runtime = Builder::new_current_thread().build().unwrap();
+        runtime.block_on(service.oneshot(request)).unwrap()
+    }
+
+    /// Respond with a set of `Set-Cookie` headers.
+    fn respond(set_cookies: &[&str]) -> Ready<Result<Response<()>, Infallible>> {
+        let mut response = Response::new(());
+        for set_cookie in set_cookies {
+            response.headers_mut().append(SET_COOKIE, set_cookie.parse().unwrap());
+        }
+        ready(Ok(response))
+    }
+
+    #[test]
+    fn request_and_response() {
+        let jar: Arc<SharedJar<ClockFn>> = Arc::new(Jar::default().into());
+        let layer = CookieLayer::new(jar.clone());
+        let sent = Arc::new(Mutex::new(Vec::new()));
+
+        let service = {
+            let sent = sent.clone();
+            layer.layer(service_fn(move |request: Request<()>| {
+                let cookies = request.headers().get(COOKIE).cloned();
+                sent.lock().unwrap().push(cookies);
+                match request.uri().path() {
+                    "/login" => respond(&["SID=31d4d96e407aad42; Path=/", "lang=en-US"]),
+                    _ => respond(&[]),
+                }
+            }))
+        };
+
+        for &uri in &["https://example.com/login", "https://example.com/account", "/relative"] {
+            send(service.clone(), Request::get(uri).body(()).unwrap());
+        }
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent[0], None);
+        assert_eq!(sent[1].as_ref().unwrap(), "SID=31d4d96e407aad42; lang=en-US");
+        assert_eq!(sent[2], None);
+    }
+
+    #[test]
+    fn inner_redirects() {
+        let jar: Arc<SharedJar<ClockFn>> = Arc::new(Jar::default().into());
+
+        // The wrapped service follows a redirect to another host and reports where the final
+        // response came from.
+        let service = CookieLayer::new(jar.clone()).layer(service_fn(|_: Request<()>| {
+            let mut response = Response::new(());
+            response.headers_mut().append(SET_COOKIE, "SID=1".parse().unwrap());
+            let uri = "https://accounts.example/callback".parse().unwrap();
+            response.extensions_mut().insert(ResponseUri(uri));
+            ready(Ok::<_, Infallible>(response))
+        }));
+        send(service, Request::get("https://example.com/login").body(()).unwrap());
+
+        let jar = jar.lock();
+        let count = |url: &str| jar.url_matches(&url.parse().unwrap(), None).count();
+        assert_eq!(count("https://accounts.example/"), 1);
+        assert_eq!(count("https://example.com/"), 0);
+    }
+
+    #[test]
+    fn follow_redirects() {
+        let jar: Arc<SharedJar<ClockFn>> = Arc::new(Jar::default().into());
+        let sent = Arc::new(Mutex::new(Vec::new()));
+
+        let inner = {
+            let sent = sent.clone();
+            service_fn(move |request: Request<()>| {
+                let uri = request.uri().to_string();
+                let cookies = request.headers().get(COOKIE).cloned();
+                let authorized = request.headers().contains_key(AUTHORIZATION);
+                sent.lock().unwrap().push((request.method().clone(), cookies, authorized));
+
+                let (status, location, set_cookie) = match uri.as_str() {
+                    "https://example.com/login" => {
+                        (StatusCode::FOUND, "https://accounts.example/authorize", "state=1")
+                    }
+                    "https://accounts.example/authorize" => {
+                        (StatusCode::SEE_OTHER, "/done", "SSO=1")
+                    }
+                    "https://accounts.example/done" => {
+                        (StatusCode::TEMPORARY_REDIRECT, "https://example.com/callback", "")
+                    }
+                    _ => (StatusCode::OK, "", "SID=1"),
+                };
+
+                let mut response = Response::new(());
+                *response.status_mut() = status;
+                if !location.is_empty() {
+                    response.headers_mut().insert(LOCATION, location.parse().unwrap());
+                }
+                if !set_cookie.is_empty() {
+                    response.headers_mut().append(SET_COOKIE, set_cookie.parse().unwrap());
+                }
+                ready(Ok::<_, Infallible>(response))
+            })
+        };
+
+        let login = || {
+            Request::post("https://example.com/login")
+                .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+                .body(())
+                .unwrap()
+        };
+
+        let other_jar: Arc<SharedJar<ClockFn>> = Arc::new(Jar::default().into());
+        let limited = CookieLayer::new(other_jar).follow_redirects(1).layer(inner.clone());
+        assert_eq!(send(limited, login()).status(), StatusCode::SEE_OTHER);
+
+        sent.lock().unwrap().clear();
+        let service = CookieLayer::new(jar.clone()).follow_redirects(10).layer(inner);
+        assert_eq!(send(service, login()).status(), StatusCode::OK);
+
+        let sent = sent.lock().unwrap();
+        let methods: Vec<_> = sent.iter().map(|sent| sent.0.clone()).collect();
+        assert_eq!(methods, [Method::POST, Method::GET, Method::GET, Method::GET]);
+        // Cookies set by the intermediate hops are sent with the hops that follow them.
+        assert_eq!(sent[1].1, None);
+        assert_eq!(sent[2].1.as_ref().unwrap(), "SSO=1");
+        assert_eq!(sent[3].1.as_ref().unwrap(), "state=1");
+        let authorized: Vec<_> = sent.iter().map(|sent| sent.2).collect();
+        assert_eq!(authorized, [true, false, false, false]);
+
+        let jar = jar.lock();
+        let names = |url: &str| {
+            let mut names: Vec<_> = jar
+                .url_matches(&url.parse().unwrap(), None)
+                .map(|cookie| cookie.pair().name().to_owned())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names("https://example.com/"), ["SID", "state"]);
+        assert_eq!(names("https://accounts.example/"), ["SSO"]);
+    }
+
+    #[test]
+    fn outer_redirects() {
+        let jar: Arc<SharedJar<ClockFn>> = Arc::new(Jar::default().into());
+        let service = CookieLayer::new(jar.clone()).layer(service_fn(|request: Request<()>| {
+            match request.uri().path() {
+                "/login" => {
+                    let mut response = Response::new(());
+                    *response.status_mut() = StatusCode::FOUND;
+                    response.headers_mut().insert(LOCATION, "/account".parse().unwrap());
+                    response.headers_mut().append(SET_COOKIE, "SID=1".parse().unwrap());
+                    ready(Ok::<_, Infallible>(response))
+                }
+                _ => {
+                    let cookies = request.headers().get(COOKIE).cloned();
+                    let mut response = Response::new(());
+                    if let Some(cookies) = cookies {
+                        response.headers_mut().insert(COOKIE, cookies);
+                    }
+                    ready(Ok(response))
+                }
+            }
+        }));
+
+        // A redirect follower around the layer sends the next hop with the headers of the first.
+        let first = Request::get("https://example.com/login").body(()).unwrap();
+        assert_eq!(send(service.clone(), first).status(), StatusCode::FOUND);
+        let second = Request::get("https://example.com/account")
+            .header(COOKIE, "SID=0")
+            .body(())
+            .unwrap();
+        assert_eq!(send(service, second).headers()[COOKIE], "SID=1");
+    }
+}
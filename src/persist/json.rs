@@ -0,0 +1,455 @@
+//! A minimal JSON encoder and decoder for the lines of a saved jar.
+//!
+//! Only the subset of JSON used by the format is supported: objects, arrays, strings, integers,
+//! booleans and null.
+
+use std::fmt::Write;
+use std::str::from_utf8;
+
+use ::error::*;
+
+/// The deepest nesting of objects and arrays accepted before a line is treated as malformed.
+const MAX_DEPTH: usize = 16;
+
+/// A decoded JSON value.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Get the last member of an object with a given key.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match *self {
+            Value::Object(ref members) => members
+                .iter()
+                .rev()
+                .find(|member| member.0 == key)
+                .map(|member| &member.1),
+            _ => None,
+        }
+    }
+
+    /// Get the value as a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref value) => Some(value.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Get the value as a boolean.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the value as an integer.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Integer(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the value as an array.
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match *self {
+            Value::Array(ref values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a line containing a single JSON value.
+pub fn parse(source: &str) -> Result<Value> {
+    let mut parser = Parser {
+        remaining: source.as_bytes(),
+        depth: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    ensure!(parser.remaining.is_empty(), malformed("trailing characters"));
+    Ok(value)
+}
+
+/// An error describing why a line could not be decoded.
+fn malformed(reason: &'static str) -> ErrorKind {
+    ErrorKind::MalformedEntry(reason)
+}
+
+/// Recursive descent parser over the remaining bytes of a line.
+struct Parser<'s> {
+    remaining: &'s [u8],
+    /// The number of objects and arrays enclosing the current value.
+    depth: usize,
+}
+
+impl<'s> Parser<'s> {
+    /// Skip over any insignificant whitespace.
+    fn skip_whitespace(&mut self) {
+        while let Some(&byte) = self.remaining.first() {
+            match byte {
+                b' ' | b'\t' | b'\r' | b'\n' => self.remaining = &self.remaining[1..],
+                _ => break,
+            }
+        }
+    }
+
+    /// Look at the next significant byte.
+    fn peek(&mut self) -> Result<u8> {
+        self.skip_whitespace();
+        match self.remaining.first() {
+            Some(&byte) => Ok(byte),
+            None => bail!(malformed("unexpected end of line")),
+        }
+    }
+
+    /// Take the next byte.
+    fn next(&mut self) -> Result<u8> {
+        match self.remaining.first() {
+            Some(&byte) => {
+                self.remaining = &self.remaining[1..];
+                Ok(byte)
+            }
+            None => bail!(malformed("unexpected end of line")),
+        }
+    }
+
+    /// Require that the next significant byte is a given delimiter.
+    fn expect(&mut self, delimiter: u8) -> Result<()> {
+        ensure!(self.peek()? == delimiter, malformed("missing delimiter"));
+        self.remaining = &self.remaining[1..];
+        Ok(())
+    }
+
+    /// Take a literal keyword.
+    fn literal(&mut self, literal: &[u8], value: Value) -> Result<Value> {
+        ensure!(self.remaining.starts_with(literal), malformed("unknown literal"));
+        self.remaining = &self.remaining[literal.len()..];
+        Ok(value)
+    }
+
+    /// Take the next value.
+    fn value(&mut self) -> Result<Value> {
+        match self.peek()? {
+            b'{' => self.nested(Parser::object),
+            b'[' => self.nested(Parser::array),
+            b'"' => self.string().map(Value::String),
+            b't' => self.literal(b"true", Value::Bool(true)),
+            b'f' => self.literal(b"false", Value::Bool(false)),
+            b'n' => self.literal(b"null", Value::Null),
+            b'-' | b'0'..=b'9' => self.integer(),
+            _ => bail!(malformed("unexpected character")),
+        }
+    }
+
+    /// Take an object or array, limiting how deeply they are nested.
+    fn nested(&mut self, take: fn(&mut Parser<'s>) -> Result<Value>) -> Result<Value> {
+        ensure!(self.depth < MAX_DEPTH, malformed("nested too deeply"));
+        self.depth += 1;
+        let value = take(self);
+        self.depth -= 1;
+        value
+    }
+
+    /// Take an object.
+    fn object(&mut self) -> Result<Value> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+
+        if self.peek()? == b'}' {
+            self.remaining = &self.remaining[1..];
+            return Ok(Value::Object(members));
+        }
+
+        loop {
+            ensure!(self.peek()? == b'"', malformed("expected a member name"));
+            let name = self.string()?;
+            self.expect(b':')?;
+            members.push((name, self.value()?));
+
+            match self.peek()? {
+                b',' => self.remaining = &self.remaining[1..],
+                b'}' => {
+                    self.remaining = &self.remaining[1..];
+                    return Ok(Value::Object(members));
+                }
+                _ => bail!(malformed("missing delimiter")),
+            }
+        }
+    }
+
+    /// Take an array.
+    fn array(&mut self) -> Result<Value> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+
+        if self.peek()? == b']' {
+            self.remaining = &self.remaining[1..];
+            return Ok(Value::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+
+            match self.peek()? {
+                b',' => self.remaining = &self.remaining[1..],
+                b']' => {
+                    self.remaining = &self.remaining[1..];
+                    return Ok(Value::Array(values));
+                }
+                _ => bail!(malformed("missing delimiter")),
+            }
+        }
+    }
+
+    /// Take an integer.
+    fn integer(&mut self) -> Result<Value> {
+        let negative = self.remaining.first() == Some(&b'-');
+        let length = self.remaining
+            .iter()
+            .skip(negative as usize)
+            .take_while(|byte| byte.is_ascii_digit())
+            .count() + negative as usize;
+        let (digits, remaining) = self.remaining.split_at(length);
+        self.remaining = remaining;
+
+        match from_utf8(digits).ok().and_then(|digits| digits.parse().ok()) {
+            Some(value) => Ok(Value::Integer(value)),
+            None => bail!(malformed("invalid integer")),
+        }
+    }
+
+    /// Take a string.
+    fn string(&mut self) -> Result<String> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => bail!(malformed("invalid escape")),
+                    };
+                    let mut encoded = [0; 4];
+                    bytes.extend_from_slice(escaped.encode_utf8(&mut encoded).as_bytes());
+                }
+                byte if byte < 0x20 => bail!(malformed("unescaped control character")),
+                byte => bytes.push(byte),
+            }
+        }
+
+        match String::from_utf8(bytes) {
+            Ok(string) => Ok(string),
+            Err(_) => bail!(malformed("invalid UTF-8")),
+        }
+    }
+
+    /// Take the hexadecimal digits of a `\u` escape, including a trailing low surrogate.
+    fn unicode_escape(&mut self) -> Result<char> {
+        let high = self.hex_digits()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            ensure!(self.remaining.starts_with(b"\\u"), malformed("unpaired surrogate"));
+            self.remaining = &self.remaining[2..];
+            let low = self.hex_digits()?;
+            ensure!((0xDC00..0xE000).contains(&low), malformed("unpaired surrogate"));
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+
+        match ::std::char::from_u32(code) {
+            Some(character) => Ok(character),
+            None => bail!(malformed("invalid unicode escape")),
+        }
+    }
+
+    /// Take four hexadecimal digits.
+    fn hex_digits(&mut self) -> Result<u32> {
+        ensure!(self.remaining.len() >= 4, malformed("invalid unicode escape"));
+        let (digits, remaining) = self.remaining.split_at(4);
+        self.remaining = remaining;
+
+        match from_utf8(digits).ok().and_then(|digits| u32::from_str_radix(digits, 16).ok()) {
+            Some(value) => Ok(value),
+            None => bail!(malformed("invalid unicode escape")),
+        }
+    }
+}
+
+/// Writer for an object on a single line.
+#[derive(Debug)]
+pub struct Object {
+    buffer: String,
+}
+
+impl Object {
+    /// Start a new empty object.
+    pub fn new() -> Object {
+        Object {
+            buffer: String::from("{"),
+        }
+    }
+
+    /// Start a new member of the object.
+    fn member(mut self, key: &str) -> Object {
+        if self.buffer.len() > 1 {
+            self.buffer.push(',');
+        }
+        write_string(&mut self.buffer, key);
+        self.buffer.push(':');
+        self
+    }
+
+    /// Add a string member.
+    pub fn string(self, key: &str, value: &str) -> Object {
+        let mut object = self.member(key);
+        write_string(&mut object.buffer, value);
+        object
+    }
+
+    /// Add an integer member.
+    pub fn integer(self, key: &str, value: i64) -> Object {
+        let mut object = self.member(key);
+        write!(object.buffer, "{}", value).unwrap();
+        object
+    }
+
+    /// Add a boolean member.
+    pub fn boolean(self, key: &str, value: bool) -> Object {
+        let mut object = self.member(key);
+        object.buffer.push_str(if value { "true" } else { "false" });
+        object
+    }
+
+    /// Add a member that is an array of strings.
+    pub fn strings<'v, I>(self, key: &str, values: I) -> Object
+    where
+        I: IntoIterator<Item = &'v str>,
+    {
+        let mut object = self.member(key);
+        object.buffer.push('[');
+        for (index, value) in values.into_iter().enumerate() {
+            if index > 0 {
+                object.buffer.push(',');
+            }
+            write_string(&mut object.buffer, value);
+        }
+        object.buffer.push(']');
+        object
+    }
+
+    /// Complete the object.
+    pub fn finish(mut self) -> String {
+        self.buffer.push('}');
+        self.buffer
+    }
+}
+
+/// Write a quoted and escaped string.
+fn write_string(buffer: &mut String, value: &str) {
+    buffer.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => buffer.push_str("\\\""),
+            '\\' => buffer.push_str("\\\\"),
+            '\n' => buffer.push_str("\\n"),
+            '\r' => buffer.push_str("\\r"),
+            '\t' => buffer.push_str("\\t"),
+            character if (character as u32) < 0x20 || character == '\u{7f}' => {
+                write!(buffer, "\\u{:04x}", character as u32).unwrap();
+            }
+            character => buffer.push(character),
+        }
+    }
+    buffer.push('"');
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let line = Object::new()
+            .string("text", "quote \" slash \\ tab \t bell \u{7} snow \u{2603}")
+            .integer("negative", -1_500_000_000)
+            .boolean("flag", true)
+            .strings("list", vec!["a", "b=c"])
+            .finish();
+
+        let expected = Value::Object(vec![
+            (
+                "text".to_owned(),
+                Value::String("quote \" slash \\ tab \t bell \u{7} snow \u{2603}".to_owned()),
+            ),
+            ("negative".to_owned(), Value::Integer(-1_500_000_000)),
+            ("flag".to_owned(), Value::Bool(true)),
+            (
+                "list".to_owned(),
+                Value::Array(vec![
+                    Value::String("a".to_owned()),
+                    Value::String("b=c".to_owned()),
+                ]),
+            ),
+        ]);
+
+        assert_eq!(parse(&line).unwrap(), expected);
+    }
+
+    #[test]
+    fn parse_values() {
+        let checks = [
+            (r#" { } "#, Value::Object(vec![])),
+            (r#"[null, false]"#, Value::Array(vec![Value::Null, Value::Bool(false)])),
+            (r#""\u00e9\ud83c\udf6a\/""#, Value::String("\u{e9}\u{1f36a}/".to_owned())),
+            (r#"0"#, Value::Integer(0)),
+        ];
+
+        for &(line, ref expected) in checks.iter() {
+            assert_eq!(&parse(line).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn reject_malformed() {
+        let checks = [
+            "", "{", "{\"a\" 1}", "{\"a\":1,}", "[1 2]", "\"open", "\"\\x\"", "\"\\ud83c\"",
+            "tru", "1.5", "-", "{} {}",
+        ];
+
+        for line in checks.iter() {
+            assert!(parse(line).is_err(), "Parsed malformed line {:?}", line);
+        }
+    }
+
+    #[test]
+    fn limit_nesting() {
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+
+        let error = parse(&nested(100_000)).unwrap_err();
+        match *error.kind() {
+            ErrorKind::MalformedEntry("nested too deeply") => {}
+            ref other => panic!("Expected a nesting error, got {:?}", other),
+        }
+    }
+}
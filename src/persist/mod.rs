@@ -0,0 +1,640 @@
+//! Saving and loading the contents of a jar.
+//!
+//! A saved jar is a text file with one JSON object per line. The first line is a header that
+//! identifies the format and its version:
+//!
+//! ```text
+//! {"format":"cookie-jar","version":1}
+//! ```
+//!
+//! Every following line holds a single cookie, shown here wrapped for readability:
+//!
+//! ```text
+//! {"host":"example.com","host_only":false,"path":"/","pair":"SID=31d4d96e407aad42",
+//!  "expires":2000000000,"secure":true,"http_only":true,"same_site":"Lax","partitioned":false,
+//!  "created":1500000000,"last_accessed":1600000000,"extensions":["Priority=High"]}
+//! ```
+//!
+//! | Member          | Type             | Meaning                                             |
+//! |-----------------|------------------|-----------------------------------------------------|
+//! | `host`          | string           | Domain name or IP address of the cookie             |
+//! | `host_only`     | boolean          | The cookie does not apply to subdomains of `host`   |
+//! | `path`          | string           | Path the cookie applies to                          |
+//! | `pair`          | string           | The `name=value` pair of the cookie                 |
+//! | `expires`       | integer          | Expiry in seconds since the Unix epoch              |
+//! | `secure`        | boolean          | The cookie requires a secure connection             |
+//! | `http_only`     | boolean          | The cookie is only sent with HTTP requests          |
+//! | `same_site`     | string           | One of `Strict`, `Lax` or `None`                    |
+//! | `partitioned`   | boolean          | The cookie has the `Partitioned` attribute          |
+//! | `partition`     | string           | Site of the top-level page the cookie is stored for |
+//! | `created`       | integer          | Creation time in seconds since the Unix epoch       |
+//! | `last_accessed` | integer          | Last access in seconds since the Unix epoch         |
+//! | `extensions`    | array of strings | Unrecognised attributes of the cookie               |
+//!
+//! `host`, `host_only`, `path` and `pair` are required. Every other member may be omitted, in
+//! which case booleans are false and the remaining attributes are unset. A cookie without
+//! `expires` is a session cookie. Unknown members are ignored.
+
+mod json;
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::process;
+use std::str::from_utf8;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use url::Host;
+
+use ::cookie::{Builder, Cookie, Expires, SameSite};
+use ::datetime::{from_unix_seconds, unix_seconds};
+use ::error::*;
+use ::jar::{Clock, Jar};
+use ::store::CookieStore;
+use self::json::{Object, Value};
+
+/// The name of the format in the header.
+const FORMAT: &str = "cookie-jar";
+
+/// The current version of the format.
+const VERSION: i64 = 1;
+
+/// The number of saves started by this process, used to name their temporary files.
+static SAVES: AtomicUsize = AtomicUsize::new(0);
+
+/// Options controlling which cookies are saved.
+///
+/// By default neither expired nor session cookies are saved.
+#[derive(Debug, Default, Clone)]
+pub struct SaveOptions {
+    /// Save cookies that have expired.
+    expired: bool,
+
+    /// Save cookies that expire at the end of the session.
+    session: bool,
+}
+
+impl SaveOptions {
+    /// Create the default save options.
+    pub fn new() -> SaveOptions {
+        SaveOptions::default()
+    }
+
+    /// Set whether cookies that have expired are saved.
+    pub fn expired(self, expired: bool) -> SaveOptions {
+        SaveOptions {
+            expired,
+            ..
+            self
+        }
+    }
+
+    /// Set whether cookies that expire at the end of the session are saved.
+    pub fn session(self, session: bool) -> SaveOptions {
+        SaveOptions {
+            session,
+            ..
+            self
+        }
+    }
+}
+
+/// The outcome of loading a saved jar.
+#[derive(Debug, Default)]
+pub struct Loaded {
+    /// The number of cookies added to the jar.
+    loaded: usize,
+
+    /// The lines that could not be loaded.
+    malformed: Vec<Malformed>,
+}
+
+impl Loaded {
+    /// Get the number of cookies added to the jar.
+    pub fn loaded(&self) -> usize {
+        self.loaded
+    }
+
+    /// Get the lines that could not be loaded.
+    pub fn malformed(&self) -> &[Malformed] {
+        &self.malformed
+    }
+}
+
+/// A line of a saved jar that could not be loaded.
+#[derive(Debug)]
+pub struct Malformed {
+    /// The line number, starting from 1 for the header.
+    line: usize,
+
+    /// The reason the line could not be loaded.
+    error: Error,
+}
+
+impl Malformed {
+    /// Get the line number, starting from 1 for the header.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Get the reason the line could not be loaded.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+}
+
+impl<T: Clock, S: CookieStore> Jar<T, S> {
+    /// Save the cookies in the jar to a file.
+    ///
+    /// Expired and session cookies are not saved. The file is replaced atomically so a failed
+    /// save leaves any previous contents intact.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.save_with(path, &SaveOptions::default())
+    }
+
+    /// Save the cookies in the jar to a file with a given set of options.
+    pub fn save_with<P: AsRef<Path>>(&self, path: P, options: &SaveOptions) -> Result<()> {
+        let path = path.as_ref();
+        let temporary = temporary_path(path)?;
+
+        let result = self
+            .write_entries(&temporary, options)
+            .and_then(|()| Ok(fs::rename(&temporary, path)?));
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+
+        result?;
+        Ok(sync_parent(path)?)
+    }
+
+    /// Write the header and cookies to a file and flush them to disk.
+    fn write_entries(&self, path: &Path, options: &SaveOptions) -> Result<()> {
+        let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        let mut file = BufWriter::new(file);
+        writeln!(file, "{}", header())?;
+
+        let now = self.now();
+        for (partition, cookie) in self.partitioned_cookies() {
+            let session = *cookie.expiry() == Expires::Never;
+            if (!options.expired && cookie.expired_since(now)) || (!options.session && session) {
+                continue;
+            }
+
+            writeln!(file, "{}", encode(&cookie, partition.as_deref()))?;
+        }
+
+        let file = file.into_inner().map_err(|error| error.into_error())?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Add the cookies from a saved jar to the jar.
+    ///
+    /// Lines that do not hold a valid cookie are skipped and listed in the outcome. The load
+    /// only fails if the file cannot be read or does not start with a supported header. Cookies
+    /// that have already expired are dropped and not counted as loaded.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<Loaded> {
+        let mut lines = BufReader::new(File::open(path)?).split(b'\n');
+
+        match lines.next() {
+            Some(header) => check_header(&header?)?,
+            None => bail!(ErrorKind::MissingHeader),
+        }
+
+        let mut loaded = Loaded::default();
+        for (index, line) in lines.enumerate() {
+            let line = line?;
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            match decode(&line) {
+                Ok((cookie, partition)) => {
+                    if self.add_to_partition(cookie, partition) {
+                        loaded.loaded += 1;
+                    }
+                }
+                Err(error) => loaded.malformed.push(Malformed {
+                    line: index + 2,
+                    error,
+                }),
+            }
+        }
+
+        Ok(loaded)
+    }
+}
+
+/// Get a unique path for the temporary file written before replacing a saved jar.
+///
+/// The name includes the process and a count of its saves so that concurrent saves of the same
+/// jar never write to the same temporary file.
+fn temporary_path(path: &Path) -> Result<::std::path::PathBuf> {
+    let name = match path.file_name() {
+        Some(name) => name,
+        None => bail!(io::Error::new(io::ErrorKind::InvalidInput, "path has no file name")),
+    };
+
+    let mut temporary = OsString::from(".");
+    temporary.push(name);
+    let save = SAVES.fetch_add(1, Ordering::Relaxed);
+    temporary.push(format!(".{}.{}.tmp", process::id(), save));
+    Ok(path.with_file_name(temporary))
+}
+
+/// Flush the rename of a file to disk by syncing the directory that contains it.
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+/// Directories cannot be opened to be synced on other platforms.
+#[cfg(not(unix))]
+fn sync_parent(_: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// The header line of the current format.
+fn header() -> String {
+    Object::new()
+        .string("format", FORMAT)
+        .integer("version", VERSION)
+        .finish()
+}
+
+/// Check that the header line describes a supported format.
+fn check_header(line: &[u8]) -> Result<()> {
+    let header = from_utf8(line)
+        .ok()
+        .and_then(|line| json::parse(line).ok())
+        .and_then(|header| match header.get("format").and_then(Value::as_str) {
+            Some(FORMAT) => Some(header),
+            _ => None,
+        });
+
+    let version = match header {
+        Some(header) => header.get("version").and_then(Value::as_i64),
+        None => bail!(ErrorKind::MissingHeader),
+    };
+
+    match version {
+        Some(VERSION) => Ok(()),
+        Some(version) => bail!(ErrorKind::UnsupportedVersion(version)),
+        None => bail!(ErrorKind::MissingHeader),
+    }
+}
+
+/// Encode a cookie as a line.
+fn encode(cookie: &Cookie, partition: Option<&Host>) -> String {
+    let mut object = Object::new()
+        .string("host", &cookie.host().to_string())
+        .boolean("host_only", cookie.host_only())
+        .string("path", cookie.path())
+        .string("pair", cookie.pair().as_str());
+
+    if let Expires::AtUtc(time) = *cookie.expiry() {
+        object = object.integer("expires", unix_seconds(time));
+    }
+
+    object = object
+        .boolean("secure", cookie.secure())
+        .boolean("http_only", cookie.http_only());
+
+    if let Some(same_site) = cookie.same_site() {
+        object = object.string("same_site", &same_site.to_string());
+    }
+
+    object = object.boolean("partitioned", cookie.partitioned());
+
+    if let Some(partition) = partition {
+        object = object.string("partition", &partition.to_string());
+    }
+
+    if let Some(time) = cookie.creation_time() {
+        object = object.integer("created", unix_seconds(time));
+    }
+
+    if let Some(time) = cookie.last_access_time() {
+        object = object.integer("last_accessed", unix_seconds(time));
+    }
+
+    if !cookie.extensions().is_empty() {
+        object = object.strings("extensions", cookie.extensions().iter().map(|e| e.as_str()));
+    }
+
+    object.finish()
+}
+
+/// Decode a line into a cookie and the partition it is stored in.
+fn decode(line: &[u8]) -> Result<(Cookie, Option<Host>)> {
+    let line = match from_utf8(line) {
+        Ok(line) => line,
+        Err(_) => bail!(ErrorKind::MalformedEntry("invalid UTF-8")),
+    };
+    let entry = json::parse(line)?;
+
+    let host = required(&entry, "host", Value::as_str)?;
+    let mut builder = if required(&entry, "host_only", Value::as_bool)? {
+        Builder::new().host_str(host)
+    } else {
+        Builder::new().domain(host)
+    };
+
+    builder = builder
+        .path(required(&entry, "path", Value::as_str)?)
+        .pair_str(required(&entry, "pair", Value::as_str)?)
+        .secure(optional(&entry, "secure", Value::as_bool)?.unwrap_or(false))
+        .http_only(optional(&entry, "http_only", Value::as_bool)?.unwrap_or(false))
+        .partitioned(optional(&entry, "partitioned", Value::as_bool)?.unwrap_or(false));
+
+    if let Some(expires) = optional(&entry, "expires", Value::as_i64)? {
+        builder = builder.expiry(from_unix_seconds(expires));
+    }
+
+    if let Some(same_site) = optional(&entry, "same_site", Value::as_str)? {
+        builder = match same_site {
+            "Strict" => builder.same_site(SameSite::Strict),
+            "Lax" => builder.same_site(SameSite::Lax),
+            "None" => builder.same_site(SameSite::None),
+            _ => bail!(ErrorKind::MalformedEntry("unknown same_site")),
+        };
+    }
+
+    if let Some(created) = optional(&entry, "created", Value::as_i64)? {
+        builder = builder.creation_time(from_unix_seconds(created));
+    }
+
+    if let Some(last_accessed) = optional(&entry, "last_accessed", Value::as_i64)? {
+        builder = builder.last_access_time(from_unix_seconds(last_accessed));
+    }
+
+    if let Some(extensions) = optional(&entry, "extensions", Value::as_array)? {
+        for extension in extensions {
+            match extension.as_str() {
+                Some(extension) => builder = builder.extension_str(extension),
+                None => bail!(ErrorKind::MalformedEntry("extensions must be strings")),
+            }
+        }
+    }
+
+    let partition = match optional(&entry, "partition", Value::as_str)? {
+        Some(partition) => Some(Host::parse(partition)?),
+        None => None,
+    };
+
+    Ok((builder.build_cookie()?, partition))
+}
+
+/// Get a required member of an entry.
+fn required<'v, F, V>(entry: &'v Value, key: &'static str, convert: F) -> Result<V>
+where
+    F: Fn(&'v Value) -> Option<V>,
+{
+    match optional(entry, key, convert)? {
+        Some(value) => Ok(value),
+        None => bail!(ErrorKind::MalformedEntry(key)),
+    }
+}
+
+/// Get an optional member of an entry, failing if it has the wrong type.
+fn optional<'v, F, V>(entry: &'v Value, key: &'static str, convert: F) -> Result<Option<V>>
+where
+    F: Fn(&'v Value) -> Option<V>,
+{
+    match entry.get(key) {
+        None | Some(&Value::Null) => Ok(None),
+        Some(value) => match convert(value) {
+            Some(value) => Ok(Some(value)),
+            None => bail!(ErrorKind::MalformedEntry(key)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jar::ClockFn;
+    use std::cell::Cell;
+    use std::env::temp_dir;
+    use std::path::PathBuf;
+    use std::time::SystemTime;
+    use url::Url;
+
+    thread_local! {
+        /// The current time in seconds used by the test jars.
+        static NOW: Cell<i64> = const { Cell::new(1_700_000_000) };
+    }
+
+    /// The current time used by the test jars.
+    fn now() -> SystemTime {
+        NOW.with(|now| from_unix_seconds(now.get()))
+    }
+
+    /// A jar at a fixed time.
+    fn jar() -> Jar<ClockFn> {
+        Jar::with_clock(now as ClockFn)
+    }
+
+    /// A unique path in the temporary directory for a test.
+    fn test_path(name: &str) -> PathBuf {
+        temp_dir().join(format!("cookie-jar-{}-{}.jsonl", name, ::std::process::id()))
+    }
+
+    /// Get the names of all of the cookies matching a URL.
+    fn matching_names(jar: &Jar<ClockFn>, url: &str, top_level_site: Option<&str>) -> Vec<String> {
+        let url: Url = url.parse().unwrap();
+        let top_level_site: Option<Url> = top_level_site.map(|site| site.parse().unwrap());
+        let mut names: Vec<_> = jar
+            .url_matches(&url, top_level_site.as_ref())
+            .map(|cookie| cookie.pair().name().to_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// A jar containing cookies of every kind.
+    fn populated_jar() -> Jar<ClockFn> {
+        let origin = "https://www.example.com/".parse().unwrap();
+        let top_level_site = "https://news.example/".parse().unwrap();
+        let mut jar = jar();
+
+        // Add the cookies at a time before any of them have expired.
+        let saved_at = NOW.with(|now| now.replace(900_000_000));
+
+        let cookies = [
+            "domain=1; Domain=example.com; Path=/; Expires=Wed, 18 May 2033 03:33:20 GMT; \
+             Secure; HttpOnly; SameSite=Lax; Priority=High",
+            "host=2; Path=/account; Expires=Wed, 18 May 2033 03:33:20 GMT",
+            "expired=1; Path=/; Expires=Sun, 09 Sep 2001 01:46:40 GMT",
+            "session=1; Path=/",
+        ];
+
+        for cookie in cookies.iter() {
+            let cookie = Cookie::parse(cookie, &origin).expect("Could not parse cookie");
+            jar.add_cookie(cookie, None);
+        }
+
+        let partitioned = Builder::new()
+            .origin(&origin)
+            .pair_str("partitioned=1")
+            .expiry(from_unix_seconds(2_000_000_000))
+            .creation_time(from_unix_seconds(1_600_000_000))
+            .secure(true)
+            .partitioned(true)
+            .build_cookie()
+            .unwrap();
+        jar.add_cookie(partitioned, Some(&top_level_site));
+
+        NOW.with(|now| now.set(saved_at));
+        jar
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = test_path("save-and-load");
+        populated_jar().save(&path).expect("Could not save jar");
+
+        let mut loaded = jar();
+        let outcome = loaded.load(&path).expect("Could not load jar");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(outcome.loaded(), 3);
+        assert!(outcome.malformed().is_empty());
+
+        let account = "https://www.example.com/account/";
+        assert_eq!(matching_names(&loaded, account, None), ["domain", "host"]);
+        assert_eq!(
+            matching_names(&loaded, account, Some("https://news.example/")),
+            ["domain", "host", "partitioned"]
+        );
+        assert_eq!(matching_names(&loaded, "https://sub.example.com/", None), ["domain"]);
+
+        let mut cookies: Vec<_> = loaded.partitioned_cookies().collect();
+        cookies.sort_by_key(|(_, cookie)| cookie.pair().name().to_owned());
+
+        let (ref partition, ref domain) = cookies[0];
+        assert_eq!(*partition, None);
+        assert_eq!(domain.domain(), Some("example.com"));
+        assert!(!domain.host_only());
+        assert!(domain.secure());
+        assert!(domain.http_only());
+        assert_eq!(domain.same_site(), Some(SameSite::Lax));
+        assert_eq!(domain.extension("Priority").and_then(|e| e.value()), Some("High"));
+        assert_eq!(*domain.expiry(), Expires::AtUtc(from_unix_seconds(2_000_000_000)));
+
+        let (_, ref host) = cookies[1];
+        assert!(host.host_only());
+        assert_eq!(host.path(), "/account");
+        assert_eq!(host.pair().as_str(), "host=2");
+
+        let (ref partition, ref partitioned) = cookies[2];
+        assert_eq!(
+            partition.as_ref().map(|host| host.to_string()),
+            Some("news.example".to_owned())
+        );
+        assert!(partitioned.partitioned());
+        assert_eq!(partitioned.creation_time(), Some(from_unix_seconds(1_600_000_000)));
+    }
+
+    #[test]
+    fn save_with_options() {
+        let path = test_path("save-with-options");
+        let options = SaveOptions::new().expired(true).session(true);
+        populated_jar().save_with(&path, &options).expect("Could not save jar");
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let mut loaded = jar();
+        let outcome = loaded.load(&path).expect("Could not load jar");
+        fs::remove_file(&path).unwrap();
+
+        assert!(contents.contains("\"pair\":\"expired=1\""));
+        // The jar itself does not keep cookies that have already expired.
+        assert_eq!(outcome.loaded(), 4);
+        assert_eq!(
+            matching_names(&loaded, "https://www.example.com/", None),
+            ["domain", "session"]
+        );
+    }
+
+    #[test]
+    fn load_skips_malformed_lines() {
+        let path = test_path("malformed");
+        let deep = "[".repeat(100_000);
+        let contents = [
+            r#"{"format":"cookie-jar","version":1}"#,
+            r#"{"host":"example.com","host_only":true,"path":"/","pair":"good=1"}"#,
+            r#"{"host":"example.com","host_only":true,"path":"/","pair":"bad name=1"}"#,
+            r#"{"host":"example.com","path":"/","pair":"missing=1"}"#,
+            r#"{"host":"example.com","host_only":"yes","path":"/","pair":"type=1"}"#,
+            "",
+            "not json",
+            r#"{"host":"example.com","host_only":true,"path":"/","pair":"also=1","new":[1]}"#,
+            &deep,
+        ];
+        fs::write(&path, contents.join("\n")).unwrap();
+
+        let mut loaded = jar();
+        let outcome = loaded.load(&path).expect("Could not load jar");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(outcome.loaded(), 2);
+        let lines: Vec<_> = outcome.malformed().iter().map(Malformed::line).collect();
+        assert_eq!(lines, [3, 4, 5, 7, 9]);
+        assert_eq!(matching_names(&loaded, "https://example.com/", None), ["also", "good"]);
+    }
+
+    #[test]
+    fn load_requires_header() {
+        let checks = [
+            ("", "missing"),
+            (r#"{"host":"example.com","host_only":true,"path":"/","pair":"a=1"}"#, "missing"),
+            (r#"{"format":"cookie-jar","version":2}"#, "version"),
+        ];
+
+        for &(contents, expected) in checks.iter() {
+            let path = test_path("header");
+            fs::write(&path, contents).unwrap();
+            let result = jar().load(&path);
+            fs::remove_file(&path).unwrap();
+
+            match (result, expected) {
+                (Err(Error(ErrorKind::MissingHeader, _)), "missing") => {}
+                (Err(Error(ErrorKind::UnsupportedVersion(2), _)), "version") => {}
+                (other, _) => panic!("Unexpected outcome for {:?}: {:?}", contents, other),
+            }
+        }
+    }
+
+    #[test]
+    fn failed_save_preserves_file() {
+        // A directory in the place of the saved jar cannot be replaced by the temporary file.
+        let path = test_path("preserve");
+        let previous = path.join("previous");
+        fs::create_dir_all(&path).unwrap();
+        fs::write(&previous, "previous contents").unwrap();
+        assert!(populated_jar().save(&path).is_err());
+
+        let contents = fs::read_to_string(&previous).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+        assert_eq!(contents, "previous contents");
+
+        let prefix = format!(".{}.", path.file_name().unwrap().to_str().unwrap());
+        let leftover = fs::read_dir(temp_dir())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name().to_string_lossy().starts_with(&prefix));
+        assert!(!leftover, "The temporary file was left behind");
+    }
+
+    #[test]
+    fn unique_temporary_paths() {
+        let path = test_path("unique");
+        assert_ne!(temporary_path(&path).unwrap(), temporary_path(&path).unwrap());
+    }
+}
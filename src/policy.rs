@@ -0,0 +1,375 @@
+//! Policies deciding which cookies a jar stores and sends.
+//!
+//! A [`Jar`](../struct.Jar.html) consults its [`Policy`](trait.Policy.html) whenever a cookie is
+//! added and whenever cookies are matched against a URL. The policy is given the cookie along with
+//! the [`Context`](struct.Context.html) of the page being viewed and decides whether the cookie is
+//! accepted, rejected, kept only for the session or kept for a limited time.
+//!
+//! Policies can be combined by collecting them into a [`Policies`](struct.Policies.html), in which
+//! case the most restrictive decision is used.
+
+use std::fmt;
+use std::time::Duration;
+
+use url::{Host, Url};
+
+use ::cookie::Cookie;
+use ::error::*;
+use ::site::registrable;
+
+/// The outcome of applying a policy to a cookie.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Decision {
+    /// The cookie is stored or sent as normal.
+    Accept,
+    /// The cookie is not stored or sent.
+    Reject,
+    /// The cookie is stored but discarded at the end of the session.
+    Session,
+    /// The cookie is stored for no longer than a given duration.
+    Limit(Duration),
+}
+
+impl Decision {
+    /// Combine two decisions into the most restrictive of the two.
+    pub fn and(self, other: Decision) -> Decision {
+        use self::Decision::*;
+
+        match (self, other) {
+            (Reject, _) | (_, Reject) => Reject,
+            (Session, _) | (_, Session) => Session,
+            (Limit(a), Limit(b)) => Limit(::std::cmp::min(a, b)),
+            (Limit(limit), Accept) | (Accept, Limit(limit)) => Limit(limit),
+            (Accept, Accept) => Accept,
+        }
+    }
+}
+
+/// The circumstances under which a cookie is being stored or sent.
+#[derive(Debug, Clone)]
+pub struct Context<'a> {
+    host: Host<&'a str>,
+    first_party: Option<&'a Url>,
+}
+
+impl<'a> Context<'a> {
+    /// Create the context for a host accessed from a first-party page.
+    ///
+    /// When cookies are stored the host is that of the cookie and when cookies are sent it is the
+    /// host of the request.
+    pub fn new(host: Host<&'a str>, first_party: Option<&'a Url>) -> Context<'a> {
+        Context {
+            host,
+            first_party,
+        }
+    }
+
+    /// Get the host the cookie is being stored for or sent to.
+    pub fn host(&self) -> Host<&'a str> {
+        self.host.clone()
+    }
+
+    /// Get the URL of the page being viewed, if known.
+    pub fn first_party(&self) -> Option<&Url> {
+        self.first_party
+    }
+
+    /// Check if the host is accessed from a page on a different [site](../site/index.html).
+    ///
+    /// Without a first-party page the access is not considered third-party.
+    pub fn is_third_party(&self) -> bool {
+        match self.first_party.and_then(Url::host) {
+            Some(first_party) => registrable(first_party) != registrable(self.host()),
+            None => false,
+        }
+    }
+}
+
+/// Decides which cookies are stored and sent by a jar.
+pub trait Policy {
+    /// Decide whether to store a cookie.
+    fn store(&self, cookie: &Cookie, context: &Context) -> Decision;
+
+    /// Decide whether to send a stored cookie.
+    ///
+    /// Any decision other than `Reject` sends the cookie. By default every cookie is sent.
+    fn send(&self, cookie: &Cookie, context: &Context) -> Decision {
+        let _ = (cookie, context);
+        Decision::Accept
+    }
+}
+
+impl<P: Policy + ?Sized> Policy for Box<P> {
+    fn store(&self, cookie: &Cookie, context: &Context) -> Decision {
+        (**self).store(cookie, context)
+    }
+
+    fn send(&self, cookie: &Cookie, context: &Context) -> Decision {
+        (**self).send(cookie, context)
+    }
+}
+
+/// Accept all cookies.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AcceptAll;
+
+impl Policy for AcceptAll {
+    fn store(&self, _: &Cookie, _: &Context) -> Decision {
+        Decision::Accept
+    }
+}
+
+/// Reject cookies stored or sent in a third-party context.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BlockThirdParty;
+
+impl Policy for BlockThirdParty {
+    fn store(&self, _: &Cookie, context: &Context) -> Decision {
+        if context.is_third_party() {
+            Decision::Reject
+        } else {
+            Decision::Accept
+        }
+    }
+
+    fn send(&self, cookie: &Cookie, context: &Context) -> Decision {
+        self.store(cookie, context)
+    }
+}
+
+/// A set of domain patterns.
+///
+/// A domain pattern matches the domain itself and all of its subdomains, while an IP address
+/// pattern matches only that address.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Domains {
+    patterns: Vec<Host>,
+}
+
+impl Domains {
+    /// Parse a set of domain patterns.
+    ///
+    /// A leading `.` on a domain pattern is ignored.
+    pub fn parse<'p, I>(patterns: I) -> Result<Domains>
+    where
+        I: IntoIterator<Item = &'p str>,
+    {
+        let patterns = patterns
+            .into_iter()
+            .map(|pattern| Host::parse(pattern.trim_start_matches('.')))
+            .collect::<::std::result::Result<_, _>>()?;
+
+        Ok(Domains { patterns })
+    }
+
+    /// Check if a host matches any of the patterns.
+    pub fn matches(&self, host: &Host) -> bool {
+        self.patterns.iter().any(|pattern| match (pattern, host) {
+            (Host::Domain(pattern), Host::Domain(host)) => {
+                host == pattern
+                    || host.ends_with(pattern.as_str())
+                        && host[..host.len() - pattern.len()].ends_with('.')
+            }
+            _ => pattern == host,
+        })
+    }
+}
+
+/// Accept cookies for matching domains and defer all others to another policy.
+///
+/// This allows exceptions to be made to an otherwise restrictive policy.
+#[derive(Debug, Clone)]
+pub struct AllowDomains<P> {
+    domains: Domains,
+    otherwise: P,
+}
+
+impl<P: Policy> AllowDomains<P> {
+    /// Accept cookies for a set of domains, otherwise applying a policy.
+    pub fn new(domains: Domains, otherwise: P) -> AllowDomains<P> {
+        AllowDomains {
+            domains,
+            otherwise,
+        }
+    }
+}
+
+impl<P: Policy> Policy for AllowDomains<P> {
+    fn store(&self, cookie: &Cookie, context: &Context) -> Decision {
+        if self.domains.matches(cookie.host()) {
+            Decision::Accept
+        } else {
+            self.otherwise.store(cookie, context)
+        }
+    }
+
+    fn send(&self, cookie: &Cookie, context: &Context) -> Decision {
+        if self.domains.matches(cookie.host()) {
+            Decision::Accept
+        } else {
+            self.otherwise.send(cookie, context)
+        }
+    }
+}
+
+/// Reject cookies for matching domains.
+#[derive(Debug, Clone)]
+pub struct BlockDomains(pub Domains);
+
+impl Policy for BlockDomains {
+    fn store(&self, cookie: &Cookie, _: &Context) -> Decision {
+        if self.0.matches(cookie.host()) {
+            Decision::Reject
+        } else {
+            Decision::Accept
+        }
+    }
+
+    fn send(&self, cookie: &Cookie, context: &Context) -> Decision {
+        self.store(cookie, context)
+    }
+}
+
+/// Keep cookies for matching domains only for the current session.
+#[derive(Debug, Clone)]
+pub struct SessionDomains(pub Domains);
+
+impl Policy for SessionDomains {
+    fn store(&self, cookie: &Cookie, _: &Context) -> Decision {
+        if self.0.matches(cookie.host()) {
+            Decision::Session
+        } else {
+            Decision::Accept
+        }
+    }
+}
+
+/// Limit the lifetime of all persistent cookies.
+///
+/// Session cookies are unaffected.
+#[derive(Debug, Clone, Copy)]
+pub struct MaxLifetime(pub Duration);
+
+impl Policy for MaxLifetime {
+    fn store(&self, _: &Cookie, _: &Context) -> Decision {
+        Decision::Limit(self.0)
+    }
+}
+
+/// A collection of policies whose most restrictive decision is used.
+#[derive(Default)]
+pub struct Policies(Vec<Box<dyn Policy + Send>>);
+
+impl Policies {
+    /// Create an empty collection that accepts all cookies.
+    pub fn new() -> Policies {
+        Default::default()
+    }
+
+    /// Add a policy to the collection.
+    pub fn with<P: Policy + Send + 'static>(mut self, policy: P) -> Policies {
+        self.0.push(Box::new(policy));
+        self
+    }
+
+    /// Check if the collection has no policies and so accepts all cookies.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Policy for Policies {
+    fn store(&self, cookie: &Cookie, context: &Context) -> Decision {
+        self.0.iter().fold(Decision::Accept, |decision, policy| {
+            decision.and(policy.store(cookie, context))
+        })
+    }
+
+    fn send(&self, cookie: &Cookie, context: &Context) -> Decision {
+        self.0.iter().fold(Decision::Accept, |decision, policy| {
+            decision.and(policy.send(cookie, context))
+        })
+    }
+}
+
+impl fmt::Debug for Policies {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Policies({})", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use site::borrow_host;
+
+    /// The number of seconds in a day.
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn cookie(cookie: &str, origin: &str) -> Cookie {
+        Cookie::parse(cookie, &origin.parse().unwrap()).expect("Could not parse cookie")
+    }
+
+    fn decide<P: Policy>(policy: &P, cookie: &Cookie, first_party: &str) -> Decision {
+        let first_party = first_party.parse().unwrap();
+        policy.store(cookie, &Context::new(borrow_host(cookie.host()), Some(&first_party)))
+    }
+
+    #[test]
+    fn domain_patterns() {
+        let domains = Domains::parse(vec![".example.com", "127.0.0.1"]).unwrap();
+
+        let matches = |host| domains.matches(&Host::parse(host).unwrap());
+        assert!(matches("example.com"));
+        assert!(matches("www.example.com"));
+        assert!(matches("127.0.0.1"));
+        assert!(!matches("notexample.com"));
+        assert!(!matches("com"));
+        assert!(!matches("127.0.0.2"));
+    }
+
+    #[test]
+    fn third_party() {
+        let tracker = cookie("id=1", "https://tracker.example/");
+
+        assert_eq!(
+            decide(&BlockThirdParty, &tracker, "https://tracker.example/"),
+            Decision::Accept
+        );
+        assert_eq!(decide(&BlockThirdParty, &tracker, "https://news.example/"), Decision::Reject);
+        assert_eq!(
+            BlockThirdParty.store(&tracker, &Context::new(borrow_host(tracker.host()), None)),
+            Decision::Accept
+        );
+
+        let allowed = Domains::parse(vec!["tracker.example"]).unwrap();
+        let policy = AllowDomains::new(allowed, BlockThirdParty);
+        assert_eq!(decide(&policy, &tracker, "https://news.example/"), Decision::Accept);
+    }
+
+    #[test]
+    fn combined_policies() {
+        let policies = Policies::new()
+            .with(SessionDomains(Domains::parse(vec!["session.example"]).unwrap()))
+            .with(BlockDomains(Domains::parse(vec!["blocked.example"]).unwrap()))
+            .with(MaxLifetime(Duration::from_secs(7 * DAY)))
+            .with(MaxLifetime(Duration::from_secs(30 * DAY)));
+
+        let site = "https://news.example/";
+        let session = cookie("a=1", "https://www.session.example/");
+        let blocked = cookie("a=1", "https://blocked.example/");
+        let other = cookie("a=1", site);
+
+        assert_eq!(decide(&policies, &session, site), Decision::Session);
+        assert_eq!(decide(&policies, &blocked, site), Decision::Reject);
+        assert_eq!(decide(&policies, &other, site), Decision::Limit(Duration::from_secs(7 * DAY)));
+        assert_eq!(
+            policies.send(&blocked, &Context::new(borrow_host(blocked.host()), None)),
+            Decision::Reject
+        );
+        assert_eq!(
+            policies.send(&session, &Context::new(borrow_host(session.host()), None)),
+            Decision::Accept
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Private cookies whose values are hidden from the client.
+//!
+//! A [`Cipher`](struct.Cipher.html) encrypts the value of each cookie with AES-256-GCM, which
+//! also authenticates it so that tampering is detected. The value sent to the client is the
+//! unpadded URL-safe base64 of a random 96-bit nonce followed by the ciphertext and its tag.
+//!
+//! The name of the cookie is authenticated as associated data, so an encrypted value is only
+//! accepted for the cookie it was encrypted for and values cannot be swapped between cookies.
+//!
+//! Keys can be rotated by encrypting with a new key while still decrypting with the old ones
+//! until every cookie encrypted with them has expired.
+
+use std::fmt;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+
+use ::cookie::{Builder, Pair};
+
+/// The number of bytes in a key.
+pub const KEY_LEN: usize = 32;
+
+/// The number of bytes in a nonce.
+const NONCE_LEN: usize = 12;
+
+/// A secret key used to encrypt cookies.
+#[derive(Clone)]
+pub struct Key {
+    cipher: Aes256Gcm,
+}
+
+impl Key {
+    /// Create a key from secret bytes.
+    ///
+    /// The bytes should come from a cryptographically secure source of random bytes.
+    pub fn new(bytes: &[u8; KEY_LEN]) -> Key {
+        Key { cipher: Aes256Gcm::new(bytes.into()) }
+    }
+
+    /// Generate a random key.
+    pub fn generate() -> Key {
+        Key { cipher: Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng)) }
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Key(..)")
+    }
+}
+
+/// Encrypts cookies with one key and decrypts them with any of a set of keys.
+#[derive(Debug, Clone)]
+pub struct Cipher {
+    /// The keys used for decryption, the first of which is used for encryption.
+    keys: Vec<Key>,
+}
+
+impl Cipher {
+    /// Encrypt and decrypt cookies with a single key.
+    pub fn new(key: Key) -> Cipher {
+        Cipher { keys: vec![key] }
+    }
+
+    /// Encrypt cookies with a new key, keeping the current keys for decryption.
+    pub fn rotate(mut self, key: Key) -> Cipher {
+        self.keys.insert(0, key);
+        self
+    }
+
+    /// Also decrypt cookies with a key that is no longer used for encryption.
+    pub fn decrypt_with(mut self, key: Key) -> Cipher {
+        self.keys.push(key);
+        self
+    }
+
+    /// Encrypt the value of a cookie.
+    pub fn encrypt(&self, name: &str, value: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: value.as_bytes(),
+            aad: name.as_bytes(),
+        };
+        let ciphertext = self.keys[0]
+            .cipher
+            .encrypt(&nonce, payload)
+            .expect("Cookie values are short enough to encrypt");
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        URL_SAFE_NO_PAD.encode(sealed)
+    }
+
+    /// Decrypt the encrypted value of a cookie.
+    ///
+    /// None if the value was not encrypted for the named cookie by any of the keys.
+    pub fn decrypt_value(&self, name: &str, encrypted: &str) -> Option<String> {
+        let sealed = URL_SAFE_NO_PAD.decode(encrypted).ok()?;
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::from_slice(nonce);
+        let value = self.keys.iter().find_map(|key| {
+            let payload = Payload {
+                msg: ciphertext,
+                aad: name.as_bytes(),
+            };
+            key.cipher.decrypt(nonce, payload).ok()
+        })?;
+
+        String::from_utf8(value).ok()
+    }
+
+    /// Decrypt the value of a cookie pair received from a client.
+    ///
+    /// None if the value was not encrypted for the cookie by any of the keys.
+    pub fn decrypt(&self, pair: &Pair) -> Option<String> {
+        self.decrypt_value(pair.name(), pair.value())
+    }
+}
+
+impl Builder {
+    /// Set the key, value pair for the cookie with the value encrypted.
+    pub fn private_pair(self, cipher: &Cipher, name: &str, value: &str) -> Builder {
+        self.pair_str(&format!("{}={}", name, cipher.encrypt(name, value)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encrypt_and_decrypt() {
+        let cipher = Cipher::new(Key::new(&[1; KEY_LEN]));
+        let encrypted = cipher.encrypt("session", "user=42; role=admin");
+        assert!(!encrypted.contains("admin"));
+        assert_ne!(encrypted, cipher.encrypt("session", "user=42; role=admin"));
+
+        let decrypted = cipher.decrypt_value("session", &encrypted);
+        assert_eq!(decrypted.as_deref(), Some("user=42; role=admin"));
+        assert_eq!(cipher.decrypt_value("other", &encrypted), None);
+        assert_eq!(Cipher::new(Key::generate()).decrypt_value("session", &encrypted), None);
+
+        let truncated = &encrypted[..encrypted.len() - 2];
+        assert_eq!(cipher.decrypt_value("session", truncated), None);
+        assert_eq!(cipher.decrypt_value("session", "AAAA"), None);
+        assert_eq!(cipher.decrypt_value("session", "not base64!"), None);
+    }
+
+    #[test]
+    fn key_rotation() {
+        let old = Cipher::new(Key::new(&[1; KEY_LEN]));
+        let encrypted = old.encrypt("session", "1");
+
+        let rotated = old.clone().rotate(Key::new(&[2; KEY_LEN]));
+        assert_eq!(rotated.decrypt_value("session", &encrypted).as_deref(), Some("1"));
+        assert_eq!(old.decrypt_value("session", &rotated.encrypt("session", "1")), None);
+
+        let retired = Cipher::new(Key::generate()).decrypt_with(Key::new(&[1; KEY_LEN]));
+        assert_eq!(retired.decrypt_value("session", &encrypted).as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn private_cookies() {
+        let cipher = Cipher::new(Key::generate());
+        let set_cookie = Builder::new()
+            .private_pair(&cipher, "session", "user=42")
+            .path("/")
+            .secure(true)
+            .build_set_cookie()
+            .expect("Could not build cookie");
+        assert_eq!(cipher.decrypt(set_cookie.pair()).as_deref(), Some("user=42"));
+
+        // A value moved to another cookie is rejected.
+        let swapped: Pair = format!("admin={}", set_cookie.pair().value()).parse().unwrap();
+        assert_eq!(cipher.decrypt(&swapped), None);
+    }
+}
@@ -0,0 +1,206 @@
+//! Rewriting of the cookies set by servers behind a reverse proxy.
+//!
+//! A server behind a reverse proxy sets cookies for the domain and paths it is reached at by the
+//! proxy, which usually differ from those the client sees. A [`Rewriter`](struct.Rewriter.html)
+//! maps the `Domain` and `Path` attributes of each `Set-Cookie` header from the upstream server
+//! onto those of the proxy and can add the `Secure` attribute when the proxy terminates TLS.
+//!
+//! All other attributes of the cookie, including any extensions, are kept.
+
+use url::Host;
+
+use ::cookie::{parse_host, SetCookie};
+use ::error::*;
+
+/// A rule replacing a domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DomainRule {
+    from: String,
+    to: String,
+}
+
+/// A rule replacing the prefix of a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PathRule {
+    from: String,
+    to: String,
+}
+
+impl PathRule {
+    /// Check if the prefix of the rule ends at a segment boundary of a path.
+    fn matches(&self, path: &str) -> bool {
+        path.starts_with(self.from.as_str())
+            && (path.len() == self.from.len()
+                || self.from.ends_with('/')
+                || path[self.from.len()..].starts_with('/'))
+    }
+}
+
+/// Rewrites the `Set-Cookie` headers of an upstream server.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Rewriter {
+    /// Domains replaced by other domains.
+    domains: Vec<DomainRule>,
+    /// Path prefixes replaced by other prefixes, in the order they are tried.
+    paths: Vec<PathRule>,
+    /// Whether to add the `Secure` attribute to every cookie.
+    secure: bool,
+}
+
+/// Parse a domain given to a rule.
+fn rule_domain(domain: &str) -> Result<String> {
+    match parse_host(domain.trim_start_matches('.'))? {
+        Host::Domain(domain) => Ok(domain),
+        _ => Err(ErrorKind::HostInvalid.into()),
+    }
+}
+
+impl Rewriter {
+    /// Create a rewriter that leaves cookies unchanged.
+    pub fn new() -> Rewriter {
+        Default::default()
+    }
+
+    /// Replace the `Domain` attribute of cookies set for one domain with another domain.
+    ///
+    /// Only the exact domain is replaced and a leading `.` is ignored. IP addresses cannot be
+    /// used as a `Domain` attribute.
+    pub fn domain(mut self, from: &str, to: &str) -> Result<Rewriter> {
+        self.domains.push(DomainRule {
+            from: rule_domain(from)?,
+            to: rule_domain(to)?,
+        });
+        Ok(self)
+    }
+
+    /// Replace the start of the `Path` attribute of cookies with paths beginning with a prefix.
+    ///
+    /// A prefix only matches whole path segments, so `/api` matches `/api` and `/api/v1` but not
+    /// `/apiary`. Rules are tried in the order they were added and only the first matching rule
+    /// is applied. Cookies without a `Path` attribute are left unchanged.
+    pub fn path(mut self, from: &str, to: &str) -> Rewriter {
+        self.paths.push(PathRule {
+            from: from.to_owned(),
+            to: to.to_owned(),
+        });
+        self
+    }
+
+    /// Set whether to add the `Secure` attribute to every cookie.
+    ///
+    /// This is needed when the proxy is reached over TLS while the upstream server is not.
+    pub fn secure(self, secure: bool) -> Rewriter {
+        Rewriter {
+            secure,
+            ..
+            self
+        }
+    }
+
+    /// Rewrite a cookie set by the upstream server.
+    pub fn rewrite(&self, mut cookie: SetCookie) -> SetCookie {
+        let domain = cookie.domain().and_then(|domain| {
+            let domain = domain.trim_start_matches('.');
+            self.domains
+                .iter()
+                .find(|rule| rule.from.eq_ignore_ascii_case(domain))
+                .map(|rule| rule.to.clone())
+        });
+        if domain.is_some() {
+            cookie.set_domain(domain);
+        }
+
+        let path = cookie.path().and_then(|path| {
+            self.paths
+                .iter()
+                .find(|rule| rule.matches(path))
+                .map(|rule| format!("{}{}", rule.to, &path[rule.from.len()..]))
+        });
+        if path.is_some() {
+            cookie.set_path(path);
+        }
+
+        if self.secure {
+            cookie.set_secure(true);
+        }
+
+        cookie
+    }
+
+    /// Rewrite the value of a `Set-Cookie` header from the upstream server.
+    pub fn rewrite_header(&self, set_cookie: &str) -> Result<String> {
+        Ok(self.rewrite(SetCookie::parse(set_cookie)?).to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rewriter() -> Rewriter {
+        Rewriter::new()
+            .domain("internal.svc", "public.example.com")
+            .unwrap()
+            .path("/api/", "/app/api/")
+            .path("/", "/app/")
+            .secure(true)
+    }
+
+    #[test]
+    fn rewrite_attributes() {
+        let rewriter = rewriter();
+        let rewrite = |set_cookie| rewriter.rewrite_header(set_cookie).unwrap();
+
+        assert_eq!(
+            rewrite("SID=1; Domain=internal.svc; Path=/"),
+            "SID=1; Path=/app/; Domain=public.example.com; Secure"
+        );
+        assert_eq!(
+            rewrite("SID=1; Domain=.INTERNAL.svc; Path=/api/v1"),
+            "SID=1; Path=/app/api/v1; Domain=public.example.com; Secure"
+        );
+        assert_eq!(
+            rewrite("SID=1; Domain=other.svc; Path=/static"),
+            "SID=1; Path=/app/static; Domain=other.svc; Secure"
+        );
+        assert_eq!(rewrite("SID=1"), "SID=1; Secure");
+    }
+
+    #[test]
+    fn rewrite_whole_segments() {
+        let rewriter = Rewriter::new().path("/api", "/x");
+        let rewrite = |set_cookie| rewriter.rewrite_header(set_cookie).unwrap();
+
+        assert_eq!(rewrite("SID=1; Path=/api"), "SID=1; Path=/x");
+        assert_eq!(rewrite("SID=1; Path=/api/v1"), "SID=1; Path=/x/v1");
+        assert_eq!(rewrite("SID=1; Path=/apiary"), "SID=1; Path=/apiary");
+    }
+
+    #[test]
+    fn preserve_attributes() {
+        let rewriter = rewriter();
+        let set_cookie = "SID=31d4d96e407aad42; Path=/; Domain=internal.svc; HttpOnly; \
+                          Expires=Wed, 09 Jun 2021 10:18:14 GMT; Max-Age=3600; SameSite=Lax; \
+                          Priority=High; Flagged";
+
+        assert_eq!(
+            rewriter.rewrite_header(set_cookie).unwrap(),
+            "SID=31d4d96e407aad42; Path=/app/; Domain=public.example.com; Secure; HttpOnly; \
+             Expires=Wed, 09 Jun 2021 10:18:14 GMT; Max-Age=3600; SameSite=Lax; Priority=High; \
+             Flagged"
+        );
+        assert_eq!(
+            Rewriter::new().rewrite_header(set_cookie).unwrap(),
+            "SID=31d4d96e407aad42; Path=/; Domain=internal.svc; HttpOnly; \
+             Expires=Wed, 09 Jun 2021 10:18:14 GMT; Max-Age=3600; SameSite=Lax; Priority=High; \
+             Flagged"
+        );
+    }
+
+    #[test]
+    fn invalid_rules() {
+        assert!(Rewriter::new().domain("127.0.0.1", "example.com").is_err());
+        assert!(Rewriter::new().domain("internal.svc", "bad domain").is_err());
+        assert!(rewriter().rewrite_header("bad name=1").is_err());
+    }
+}
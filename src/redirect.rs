@@ -0,0 +1,279 @@
+//! Cookies sent and stored while following a chain of redirects.
+//!
+//! Each hop of a redirect chain must store the cookies set by its response before the `Cookie`
+//! header of the next hop is computed. A [`RedirectChain`](struct.RedirectChain.html) does this
+//! for a [`Jar`](../struct.Jar.html) while tracking whether the chain is same-site.
+//!
+//! Following [RFC6265bis section 5.2][rfc6265bis], a chain is same-site only while every URL
+//! requested so far is on the same [site](../site/index.html) as the page that initiated it. Once
+//! a redirect leaves that site the rest of the chain is cross-site, even if it later returns.
+//! Cross-site requests are sent without `Strict` cookies, and without `Lax` cookies unless the
+//! chain is a top-level navigation. Cookies with either attribute are only set by cross-site
+//! responses to top-level navigations. Cookies without a `SameSite` attribute are not restricted.
+//!
+//! Redirects are assumed to be followed with the `GET` method.
+//!
+//! [rfc6265bis]: https://datatracker.ietf.org/doc/html/draft-ietf-httpbis-rfc6265bis#section-5.2
+
+use url::Url;
+
+use ::cookie::{Cookie, SameSite};
+use ::error::*;
+use ::jar::{format_cookie_header, Clock, Jar};
+use ::site::Site;
+use ::store::{CookieStore, TreeStore};
+
+/// The state of a redirect chain being followed for a jar.
+#[derive(Debug)]
+pub struct RedirectChain<'j, T: Clock + 'j, S: CookieStore + 'j = TreeStore> {
+    jar: &'j mut Jar<T, S>,
+    /// The page the chain was started from, if any.
+    initiator: Option<Url>,
+    /// The site every request must be on for the chain to be same-site.
+    site: Option<Site>,
+    /// Whether the chain navigates the top-level page rather than loading a subresource.
+    navigation: bool,
+    /// Whether any request so far was cross-site.
+    cross_site: bool,
+    /// The URL of the current hop, None once the chain has ended.
+    url: Option<Url>,
+}
+
+impl<'j, T: Clock, S: CookieStore> RedirectChain<'j, T, S> {
+    /// Start a top-level navigation to a URL that was not initiated by any page.
+    ///
+    /// This is the case for URLs entered by a user, so the chain is same-site with the site of
+    /// its first URL.
+    pub fn new(jar: &'j mut Jar<T, S>, url: Url) -> RedirectChain<'j, T, S> {
+        RedirectChain {
+            jar,
+            initiator: None,
+            site: Site::from_url(&url),
+            navigation: true,
+            cross_site: false,
+            url: Some(url),
+        }
+    }
+
+    /// Set the page the chain was started from.
+    ///
+    /// Requests are same-site only if they are on the same site as this page.
+    pub fn initiator(self, initiator: &Url) -> RedirectChain<'j, T, S> {
+        let mut chain = RedirectChain {
+            initiator: Some(initiator.clone()),
+            site: Site::from_url(initiator),
+            ..
+            self
+        };
+        chain.cross_site = chain.cross_site || chain.is_cross_site();
+        chain
+    }
+
+    /// Set whether the chain navigates the top-level page.
+    ///
+    /// Subresources use the initiator as the top-level site of their cookies while navigations use
+    /// the URL being navigated to.
+    pub fn navigation(self, navigation: bool) -> RedirectChain<'j, T, S> {
+        RedirectChain {
+            navigation,
+            ..
+            self
+        }
+    }
+
+    /// Get the URL of the current hop.
+    ///
+    /// None once a response without a redirect has been received.
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+
+    /// Check if the current hop is a cross-site request.
+    pub fn cross_site(&self) -> bool {
+        self.cross_site
+    }
+
+    /// Get the value of the `Cookie` header for the current hop.
+    ///
+    /// None if no cookies are sent or the chain has ended.
+    pub fn cookie_header(&self) -> Option<String> {
+        let url = self.url.as_ref()?;
+        let matches = self
+            .jar
+            .url_matches(url, self.top_level_site())
+            .filter(|cookie| self.allows(cookie.same_site()));
+        format_cookie_header(matches)
+    }
+
+    /// Store the cookies from the response to the current hop and follow its redirect.
+    ///
+    /// Invalid `Set-Cookie` headers are ignored. The location is resolved against the URL of the
+    /// current hop and becomes the URL of the next hop; without a location the chain ends.
+    pub fn receive<'h, I>(&mut self, set_cookies: I, location: Option<&str>) -> Result<Option<&Url>>
+    where
+        I: IntoIterator<Item = &'h str>,
+    {
+        let url = match self.url.take() {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+
+        let top_level_site = if self.navigation { Some(&url) } else { self.initiator.as_ref() };
+        for set_cookie in set_cookies {
+            if let Ok(cookie) = Cookie::parse(set_cookie, &url) {
+                if self.accepts(cookie.same_site()) {
+                    self.jar.add_cookie(cookie, top_level_site);
+                }
+            }
+        }
+
+        if let Some(location) = location {
+            self.url = Some(url.join(location)?);
+            self.cross_site = self.cross_site || self.is_cross_site();
+        }
+
+        Ok(self.url.as_ref())
+    }
+
+    /// Replay a recorded redirect chain, getting the `Cookie` header sent with each hop.
+    ///
+    /// Each hop is given by its request URL, the `Set-Cookie` headers of its response and the
+    /// location it redirected to. The request URL of each hop replaces the location of the hop
+    /// before it, so the URLs a client actually requested are used.
+    pub fn replay<'h, I, C>(mut self, hops: I) -> Result<Vec<Option<String>>>
+    where
+        I: IntoIterator<Item = (&'h Url, C, Option<&'h str>)>,
+        C: IntoIterator<Item = &'h str>,
+    {
+        let mut headers = Vec::new();
+
+        for (url, set_cookies, location) in hops {
+            self.url = Some(url.clone());
+            self.cross_site = self.cross_site || self.is_cross_site();
+            headers.push(self.cookie_header());
+            self.receive(set_cookies, location)?;
+        }
+
+        Ok(headers)
+    }
+
+    /// Get the top-level site of cookies sent with and set by the current hop.
+    fn top_level_site(&self) -> Option<&Url> {
+        if self.navigation {
+            self.url.as_ref()
+        } else {
+            self.initiator.as_ref()
+        }
+    }
+
+    /// Check if the URL of the current hop is on a different site to the initiator.
+    fn is_cross_site(&self) -> bool {
+        let url = self.url.as_ref().and_then(Site::from_url);
+        url.is_none() || url != self.site
+    }
+
+    /// Check if a cookie with a `SameSite` attribute may be set by the current hop.
+    fn accepts(&self, same_site: Option<SameSite>) -> bool {
+        match same_site {
+            Some(SameSite::Strict) | Some(SameSite::Lax) => !self.cross_site || self.navigation,
+            Some(SameSite::None) | None => true,
+        }
+    }
+
+    /// Check if a cookie with a `SameSite` attribute may be sent with the current hop.
+    fn allows(&self, same_site: Option<SameSite>) -> bool {
+        match same_site {
+            _ if !self.cross_site => true,
+            Some(SameSite::Strict) => false,
+            Some(SameSite::Lax) => self.navigation,
+            Some(SameSite::None) | None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jar::ClockFn;
+
+    fn url(url: &str) -> Url {
+        url.parse().unwrap()
+    }
+
+    #[test]
+    fn cookies_between_hops() {
+        let mut jar: Jar<ClockFn> = Jar::default();
+        let mut chain = RedirectChain::new(&mut jar, url("https://example.com/login"));
+
+        assert_eq!(chain.cookie_header(), None);
+        let next = chain.receive(vec!["SID=1; Path=/"], Some("/account")).unwrap();
+        assert_eq!(next, Some(&url("https://example.com/account")));
+        assert_eq!(chain.cookie_header().as_deref(), Some("SID=1"));
+
+        let next = chain.receive(vec!["lang=en"], Some("https://www.example.com/home")).unwrap();
+        assert_eq!(next, Some(&url("https://www.example.com/home")));
+        assert!(!chain.cross_site());
+        assert_eq!(chain.cookie_header(), None);
+
+        assert_eq!(chain.receive(vec![], None).unwrap(), None);
+        assert_eq!(chain.cookie_header(), None);
+    }
+
+    #[test]
+    fn cross_site_redirects() {
+        let mut jar: Jar<ClockFn> = Jar::default();
+        let origin = url("https://example.com/");
+        for set_cookie in &["strict=1; SameSite=Strict", "lax=1; SameSite=Lax", "none=1"] {
+            jar.add_cookie(Cookie::parse(set_cookie, &origin).unwrap(), None);
+        }
+
+        // A same-site chain that bounces through another site becomes cross-site.
+        let hops = [
+            (url("https://example.com/"), vec![], Some("https://sso.example/")),
+            (url("https://sso.example/"), vec![], Some("https://example.com/")),
+            (url("https://example.com/"), vec!["late=1; SameSite=Strict"], None),
+        ];
+        let replay = |jar: &mut Jar<ClockFn>, navigation: bool| {
+            let hops = hops.iter().map(|&(ref url, ref set_cookies, location)| {
+                (url, set_cookies.iter().cloned(), location)
+            });
+            RedirectChain::new(jar, origin.clone())
+                .navigation(navigation)
+                .replay(hops)
+                .unwrap()
+        };
+
+        let headers = replay(&mut jar, true);
+        assert_eq!(headers[0].as_deref(), Some("strict=1; lax=1; none=1"));
+        assert_eq!(headers[1], None);
+        assert_eq!(headers[2].as_deref(), Some("lax=1; none=1"));
+
+        let headers = replay(&mut jar, false);
+        assert_eq!(headers[2].as_deref(), Some("none=1"));
+
+        // The strict cookie was set by a cross-site navigation.
+        let header = jar.cookie_header(&origin, None);
+        assert_eq!(header.as_deref(), Some("strict=1; lax=1; none=1; late=1"));
+    }
+
+    #[test]
+    fn cross_site_initiator() {
+        let mut jar: Jar<ClockFn> = Jar::default();
+        let origin = url("https://example.com/");
+        jar.add_cookie(Cookie::parse("strict=1; SameSite=Strict", &origin).unwrap(), None);
+        jar.add_cookie(Cookie::parse("lax=1; SameSite=Lax", &origin).unwrap(), None);
+
+        let initiator = url("https://news.example/");
+        let chain = RedirectChain::new(&mut jar, origin.clone()).initiator(&initiator);
+        assert!(chain.cross_site());
+        assert_eq!(chain.cookie_header().as_deref(), Some("lax=1"));
+
+        // Subresources are neither sent nor set restricted cookies.
+        let mut chain = RedirectChain::new(&mut jar, origin.clone())
+            .initiator(&initiator)
+            .navigation(false);
+        assert_eq!(chain.cookie_header(), None);
+        chain.receive(vec!["sub=1; SameSite=Lax", "any=1"], None).unwrap();
+        assert_eq!(jar.cookie_header(&origin, None).as_deref(), Some("strict=1; lax=1; any=1"));
+    }
+}
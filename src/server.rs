@@ -0,0 +1,206 @@
+//! Cookies of a request handled by a server and the changes made to them.
+//!
+//! A server reads the cookies sent by the client from the `Cookie` header of a request into
+//! [`Cookies`](struct.Cookies.html). Handlers can then add, replace and remove cookies, after which
+//! only the changes need to be sent back as `Set-Cookie` headers by taking the
+//! [`delta`](struct.Cookies.html#method.delta).
+//!
+//! A removal is sent as a cookie with an empty value that has already expired, with both an
+//! `Expires` attribute at the Unix epoch and `Max-Age=0`. The client only removes the cookie if
+//! the domain and path of the removal match those the cookie was set with.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use ::cookie::{Builder, Pair, SetCookie};
+use ::error::*;
+
+/// A change made to the cookies of a request.
+#[derive(Debug)]
+enum Change {
+    /// A cookie was added or replaced.
+    Add(SetCookie),
+    /// A cookie was removed, holding the expired cookie that removes it.
+    Remove(SetCookie),
+}
+
+impl Change {
+    /// Get the cookie sent for the change.
+    fn set_cookie(&self) -> &SetCookie {
+        match *self {
+            Change::Add(ref cookie) | Change::Remove(ref cookie) => cookie,
+        }
+    }
+
+    /// Check if the change is for the cookie with a given name, domain and path.
+    fn is_for(&self, cookie: &SetCookie) -> bool {
+        let changed = self.set_cookie();
+        changed.pair().name() == cookie.pair().name()
+            && changed.domain() == cookie.domain()
+            && changed.path() == cookie.path()
+    }
+}
+
+/// The cookies sent with a request along with the changes made to them.
+#[derive(Debug, Default)]
+pub struct Cookies {
+    /// The cookies sent by the client.
+    original: Vec<Pair>,
+    /// The changes made since, in the order they were made.
+    changes: Vec<Change>,
+}
+
+impl Cookies {
+    /// Create an empty collection for a request without cookies.
+    pub fn new() -> Cookies {
+        Default::default()
+    }
+
+    /// Read the cookies from the value of a request `Cookie` header.
+    ///
+    /// Malformed cookies are ignored.
+    pub fn parse(header: &str) -> Cookies {
+        let original = header
+            .split(';')
+            .map(str::trim)
+            .filter_map(|pair| pair.parse().ok())
+            .collect();
+
+        Cookies {
+            original,
+            changes: Vec::new(),
+        }
+    }
+
+    /// Get the current cookie with a name.
+    ///
+    /// A cookie that has been added or removed shadows one sent by the client.
+    pub fn get(&self, name: &str) -> Option<&Pair> {
+        let changed = self
+            .changes
+            .iter()
+            .rev()
+            .find(|change| change.set_cookie().pair().name() == name);
+
+        match changed {
+            Some(Change::Add(cookie)) => Some(cookie.pair()),
+            Some(Change::Remove(_)) => None,
+            None => self.original.iter().find(|pair| pair.name() == name),
+        }
+    }
+
+    /// Get the cookies sent by the client, without any changes.
+    pub fn original(&self) -> &[Pair] {
+        &self.original
+    }
+
+    /// Add a cookie, replacing any cookie with the same name, domain and path.
+    pub fn add(&mut self, cookie: SetCookie) {
+        self.changes.retain(|change| !change.is_for(&cookie));
+        self.changes.push(Change::Add(cookie));
+    }
+
+    /// Remove the cookie with a name that was set for the root path without a domain.
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        self.remove_at(name, None, Some("/"))
+    }
+
+    /// Remove the cookie with a name that was set with a given domain and path.
+    ///
+    /// A cookie that was added but not sent by the client is dropped from the changes instead.
+    pub fn remove_at(&mut self, name: &str, domain: Option<&str>, path: Option<&str>)
+        -> Result<()>
+    {
+        let mut builder = Builder::new()
+            .pair_str(&format!("{}=", name))
+            .expiry(UNIX_EPOCH)
+            .max_age(Duration::from_secs(0));
+        if let Some(domain) = domain {
+            builder = builder.domain(domain);
+        }
+        if let Some(path) = path {
+            builder = builder.path(path);
+        }
+        let removal = builder.build_set_cookie()?;
+
+        self.changes.retain(|change| !change.is_for(&removal));
+        if self.original.iter().any(|pair| pair.name() == name) {
+            self.changes.push(Change::Remove(removal));
+        }
+        Ok(())
+    }
+
+    /// Get the cookies to send in `Set-Cookie` headers for the changes, in the order they were
+    /// made.
+    pub fn delta(&self) -> impl Iterator<Item = &SetCookie> {
+        self.changes.iter().map(Change::set_cookie)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn set_cookie(set_cookie: &str) -> SetCookie {
+        SetCookie::parse(set_cookie).expect("Could not parse cookie")
+    }
+
+    fn delta(cookies: &Cookies) -> Vec<String> {
+        cookies.delta().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn parse_request() {
+        let cookies = Cookies::parse("SID=31d4d96e407aad42; lang=en-US;; bad name=1; empty=");
+        let names: Vec<_> = cookies.original().iter().map(Pair::name).collect();
+        assert_eq!(names, vec!["SID", "lang", "empty"]);
+        assert_eq!(cookies.get("lang").map(Pair::value), Some("en-US"));
+        assert!(cookies.get("missing").is_none());
+        assert_eq!(cookies.delta().count(), 0);
+    }
+
+    #[test]
+    fn changes() {
+        let mut cookies = Cookies::parse("SID=31d4d96e407aad42; lang=en-US");
+
+        cookies.add(set_cookie("lang=en-AU; Path=/"));
+        cookies.add(set_cookie("theme=dark; Path=/"));
+        cookies.add(set_cookie("lang=de-DE; Path=/"));
+        cookies.remove("SID").unwrap();
+
+        assert_eq!(cookies.get("lang").map(Pair::value), Some("de-DE"));
+        assert!(cookies.get("SID").is_none());
+        assert_eq!(
+            delta(&cookies),
+            vec![
+                "theme=dark; Path=/",
+                "lang=de-DE; Path=/",
+                "SID=; Path=/; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Max-Age=0",
+            ]
+        );
+
+        // Cookies that were only added are dropped rather than removed.
+        cookies.remove("theme").unwrap();
+        assert!(cookies.get("theme").is_none());
+        assert_eq!(delta(&cookies).len(), 2);
+
+        // Adding a removed cookie again replaces the removal.
+        cookies.add(set_cookie("SID=abc; Path=/; Secure"));
+        assert_eq!(cookies.get("SID").map(Pair::value), Some("abc"));
+        assert_eq!(delta(&cookies), vec!["lang=de-DE; Path=/", "SID=abc; Path=/; Secure"]);
+    }
+
+    #[test]
+    fn scoped_removal() {
+        let mut cookies = Cookies::parse("SID=1");
+        cookies.remove_at("SID", Some("example.com"), Some("/app")).unwrap();
+        assert_eq!(
+            delta(&cookies),
+            vec!["SID=; Path=/app; Domain=example.com; Expires=Thu, 01 Jan 1970 00:00:00 GMT; \
+                  Max-Age=0"]
+        );
+
+        let removal = set_cookie(&delta(&cookies)[0]);
+        assert!(removal.expired());
+        assert!(cookies.remove_at("SID", Some("bad domain"), None).is_err());
+    }
+}
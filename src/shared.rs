@@ -0,0 +1,120 @@
+//! A jar that can be shared between threads.
+//!
+//! HTTP clients often send requests from several threads at once, all of which need to store and
+//! read the same cookies. A [`SharedJar`](struct.SharedJar.html) guards a
+//! [`Jar`](../struct.Jar.html) with a lock so that it can be placed in an `Arc` and used from each
+//! of them.
+//!
+//! With the `reqwest` feature a shared jar implements `reqwest::cookie::CookieStore` and can be
+//! given to a client with `reqwest::ClientBuilder::cookie_provider`. The jar remains available
+//! through the `Arc` to be inspected or saved while the client is in use.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use ::jar::{Clock, Jar};
+use ::store::{CookieStore, TreeStore};
+
+/// A jar guarded by a lock.
+#[derive(Debug)]
+pub struct SharedJar<T: Clock, S: CookieStore = TreeStore> {
+    jar: Mutex<Jar<T, S>>,
+}
+
+impl<T: Clock, S: CookieStore> SharedJar<T, S> {
+    /// Share a jar between threads.
+    pub fn new(jar: Jar<T, S>) -> SharedJar<T, S> {
+        SharedJar { jar: Mutex::new(jar) }
+    }
+
+    /// Lock the jar for exclusive access.
+    ///
+    /// A jar is left in a consistent state between calls so it is still returned if a thread
+    /// panicked while holding the lock.
+    pub fn lock<'j>(&'j self) -> MutexGuard<'j, Jar<T, S>> {
+        self.jar.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Take the jar back from the lock.
+    pub fn into_inner(self) -> Jar<T, S> {
+        self.jar.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: Clock, S: CookieStore> From<Jar<T, S>> for SharedJar<T, S> {
+    fn from(jar: Jar<T, S>) -> SharedJar<T, S> {
+        SharedJar::new(jar)
+    }
+}
+
+/// Cookies are stored and matched without a top-level site as the client does not know which page
+/// a request is made from. Partitioned cookies are therefore kept unpartitioned.
+///
+/// The trait has no way to report errors, so they are dropped:
+///
+/// * a URL from the client that cannot be converted to a URL of this crate stores and sends no
+///   cookies,
+/// * a `Set-Cookie` header that cannot be parsed is ignored, as described in
+///   [RFC6265 section 5.2](https://tools.ietf.org/html/rfc6265#section-5.2), and
+/// * a `Cookie` header that is not a valid header value is not sent.
+#[cfg(feature = "reqwest")]
+impl<T, S> ::reqwest::cookie::CookieStore for SharedJar<T, S>
+where
+    T: Clock + Send,
+    S: CookieStore + Send,
+{
+    fn set_cookies(
+        &self,
+        cookie_headers: &mut dyn Iterator<Item = &::http::HeaderValue>,
+        url: &::url2::Url,
+    ) {
+        let url = match ::url::Url::parse(url.as_str()) {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+
+        let mut jar = self.lock();
+        for value in cookie_headers {
+            if let Ok(cookie) = ::headers::parse_set_cookie(value, &url) {
+                jar.add_cookie(cookie, None);
+            }
+        }
+    }
+
+    fn cookies(&self, url: &::url2::Url) -> Option<::http::HeaderValue> {
+        let url = ::url::Url::parse(url.as_str()).ok()?;
+        let header = self.lock().cookie_header(&url, None)?;
+        ::http::HeaderValue::from_str(&header).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cookie::Cookie;
+    use jar::ClockFn;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn shared_between_threads() {
+        let jar: Arc<SharedJar<ClockFn>> = Arc::new(Jar::default().into());
+
+        let threads: Vec<_> = (0..4)
+            .map(|index| {
+                let jar = jar.clone();
+                thread::spawn(move || {
+                    let origin = "https://example.com/".parse().unwrap();
+                    let cookie = Cookie::parse(&format!("c{}={}", index, index), &origin).unwrap();
+                    jar.lock().add_cookie(cookie, None);
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let jar = Arc::try_unwrap(jar).unwrap().into_inner();
+        let url = "https://example.com/".parse().unwrap();
+        assert_eq!(jar.url_matches(&url, None).count(), 4);
+    }
+}
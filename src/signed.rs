@@ -0,0 +1,205 @@
+//! Signed cookies that allow a server to detect tampering.
+//!
+//! A [`Signer`](struct.Signer.html) appends a MAC to the value of each cookie it signs and checks
+//! and removes it when the cookie is sent back. The MAC is an HMAC-SHA256 of the `name=value`
+//! pair, so a signed value is only accepted for the cookie it was signed for. It is appended to
+//! the value after a `.` in unpadded URL-safe base64:
+//!
+//! ```text
+//! SID=31d4d96e407aad42.hSVbLEVBDQ0sPS9qD8dDH3ExU8zxcIjZtnAh0hjvTWw
+//! ```
+//!
+//! Signing does not hide the value of a cookie from the client.
+//!
+//! Keys can be rotated by signing with a new key while still verifying with the old ones until
+//! every cookie signed with them has expired.
+
+use std::fmt;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use ::cookie::Builder;
+use ::error::*;
+
+/// The smallest number of bytes in a key.
+pub const MIN_KEY_LEN: usize = 32;
+
+/// The length of an encoded MAC.
+const ENCODED_MAC_LEN: usize = 43;
+
+/// A secret key used to sign cookies.
+#[derive(Clone)]
+pub struct Key {
+    bytes: Vec<u8>,
+}
+
+impl Key {
+    /// Create a key from secret bytes.
+    ///
+    /// The key must be at least [`MIN_KEY_LEN`](constant.MIN_KEY_LEN.html) bytes long and should
+    /// come from a cryptographically secure source of random bytes.
+    pub fn new(bytes: &[u8]) -> Result<Key> {
+        ensure!(bytes.len() >= MIN_KEY_LEN, ErrorKind::ShortKey(bytes.len()));
+        Ok(Key { bytes: bytes.to_vec() })
+    }
+
+    /// Start the MAC of a cookie.
+    fn mac(&self, name: &str, value: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.bytes).expect("HMAC accepts any key");
+        mac.update(name.as_bytes());
+        mac.update(b"=");
+        mac.update(value.as_bytes());
+        mac
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Key(..)")
+    }
+}
+
+/// Signs cookies with one key and verifies them with any of a set of keys.
+#[derive(Debug, Clone)]
+pub struct Signer {
+    /// The keys used for verification, the first of which is used for signing.
+    keys: Vec<Key>,
+}
+
+impl Signer {
+    /// Sign and verify cookies with a single key.
+    pub fn new(key: Key) -> Signer {
+        Signer { keys: vec![key] }
+    }
+
+    /// Sign cookies with a new key, keeping the current keys for verification.
+    pub fn rotate(mut self, key: Key) -> Signer {
+        self.keys.insert(0, key);
+        self
+    }
+
+    /// Also verify cookies with a key that is no longer used for signing.
+    pub fn verify_with(mut self, key: Key) -> Signer {
+        self.keys.push(key);
+        self
+    }
+
+    /// Sign the value of a cookie.
+    ///
+    /// Returns the value with the MAC appended.
+    pub fn sign(&self, name: &str, value: &str) -> String {
+        let mac = self.keys[0].mac(name, value).finalize().into_bytes();
+        format!("{}.{}", value, URL_SAFE_NO_PAD.encode(mac))
+    }
+
+    /// Verify the signed value of a cookie.
+    ///
+    /// Returns the value with the MAC removed, or None if it was not signed by any of the keys.
+    pub fn verify<'v>(&self, name: &str, signed: &'v str) -> Option<&'v str> {
+        let split = signed.len().checked_sub(ENCODED_MAC_LEN + 1)?;
+        if !signed.is_char_boundary(split) || signed.as_bytes()[split] != b'.' {
+            return None;
+        }
+
+        let (value, mac) = (&signed[..split], &signed[split + 1..]);
+        let mac = URL_SAFE_NO_PAD.decode(mac).ok()?;
+        let valid = self.keys.iter().any(|key| key.mac(name, value).verify_slice(&mac).is_ok());
+        if valid {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Verify the cookies in the value of a request `Cookie` header.
+    ///
+    /// Returns the `(name, value)` pairs of the cookies that were signed by any of the keys with
+    /// the MAC removed from each value. Unsigned and malformed cookies are left out.
+    pub fn verify_header<'h>(&self, header: &'h str) -> Vec<(&'h str, &'h str)> {
+        header
+            .split(';')
+            .filter_map(|pair| {
+                let pair = pair.trim();
+                let delimiter = pair.find('=')?;
+                let name = &pair[..delimiter];
+                self.verify(name, &pair[delimiter + 1..]).map(|value| (name, value))
+            })
+            .collect()
+    }
+}
+
+impl Builder {
+    /// Set the key, value pair for the cookie with the value signed.
+    pub fn signed_pair(self, signer: &Signer, name: &str, value: &str) -> Builder {
+        self.pair_str(&format!("{}={}", name, signer.sign(name, value)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(byte: u8) -> Key {
+        Key::new(&[byte; MIN_KEY_LEN]).unwrap()
+    }
+
+    #[test]
+    fn short_keys() {
+        match Key::new(&[0; MIN_KEY_LEN - 1]) {
+            Err(Error(ErrorKind::ShortKey(length), _)) => assert_eq!(length, MIN_KEY_LEN - 1),
+            other => panic!("Expected a short key error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let signer = Signer::new(key(1));
+        let signed = signer.sign("SID", "31d4d96e407aad42");
+        assert!(signed.starts_with("31d4d96e407aad42."));
+        assert_eq!(signed.len(), "31d4d96e407aad42.".len() + ENCODED_MAC_LEN);
+
+        assert_eq!(signer.verify("SID", &signed), Some("31d4d96e407aad42"));
+        assert_eq!(signer.verify("other", &signed), None);
+        assert_eq!(signer.verify("SID", "31d4d96e407aad42"), None);
+        assert_eq!(Signer::new(key(2)).verify("SID", &signed), None);
+
+        let tampered = signed.replacen("31d4", "41d4", 1);
+        assert_eq!(signer.verify("SID", &tampered), None);
+        assert_eq!(signer.verify("SID", &signed[..signed.len() - 1]), None);
+        assert_eq!(signer.verify("SID", "é.aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"), None);
+    }
+
+    #[test]
+    fn key_rotation() {
+        let old = Signer::new(key(1));
+        let signed = old.sign("SID", "1");
+
+        let rotated = old.clone().rotate(key(2));
+        assert_eq!(rotated.verify("SID", &signed), Some("1"));
+        assert_eq!(old.verify("SID", &rotated.sign("SID", "1")), None);
+
+        let retired = Signer::new(key(3)).verify_with(key(1));
+        assert_eq!(retired.verify("SID", &signed), Some("1"));
+        assert_eq!(retired.sign("SID", "1"), Signer::new(key(3)).sign("SID", "1"));
+    }
+
+    #[test]
+    fn signed_cookies() {
+        let signer = Signer::new(key(1));
+        let set_cookie = Builder::new()
+            .signed_pair(&signer, "SID", "31d4d96e407aad42")
+            .path("/")
+            .build_set_cookie()
+            .expect("Could not build cookie");
+
+        let (name, value) = set_cookie.pair().as_tuple();
+        let header = format!("lang=en-US; {}={}; forged=1.{}", name, value, "a".repeat(43));
+        assert_eq!(signer.verify_header(&header), vec![("SID", "31d4d96e407aad42")]);
+
+        let invalid = Builder::new().signed_pair(&signer, "bad name", "1").build_set_cookie();
+        assert!(invalid.is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! Sites used to decide whether a request is same-site or cross-site.
+//!
+//! The site of a domain is its registrable domain, that is the public suffix along with the label
+//! immediately before it (`www.example.co.uk` is part of the site `example.co.uk`). A domain that
+//! is itself a public suffix is its own site. IP addresses have no registrable domain so the site
+//! of an address is that exact address.
+
+use std::fmt;
+
+use psl;
+use url::{Host, Url};
+
+/// The registrable domain or IP address a host belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Site(Host);
+
+impl Site {
+    /// Get the site of a host.
+    pub fn from_host(host: &Host) -> Site {
+        Site(registrable(borrow_host(host)).to_owned())
+    }
+
+    /// Get the site of the host of a URL.
+    ///
+    /// None if the URL has no host.
+    pub fn from_url(url: &Url) -> Option<Site> {
+        url.host().map(|host| Site(registrable(host).to_owned()))
+    }
+
+    /// Get the registrable domain or IP address of the site.
+    pub fn host(&self) -> &Host {
+        &self.0
+    }
+
+    /// Convert the site into its registrable domain or IP address.
+    pub fn into_host(self) -> Host {
+        self.0
+    }
+}
+
+impl fmt::Display for Site {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Get the registrable domain or IP address of a host without copying it.
+pub(crate) fn registrable(host: Host<&str>) -> Host<&str> {
+    match host {
+        Host::Domain(domain) => {
+            let domain = domain.trim_end_matches('.');
+            Host::Domain(psl::domain_str(domain).unwrap_or(domain))
+        }
+        address => address,
+    }
+}
+
+/// Borrow the domain of a host.
+pub(crate) fn borrow_host(host: &Host) -> Host<&str> {
+    match *host {
+        Host::Domain(ref domain) => Host::Domain(domain),
+        Host::Ipv4(address) => Host::Ipv4(address),
+        Host::Ipv6(address) => Host::Ipv6(address),
+    }
+}
+
+/// Check if a request to a URL is same-site with the page at a first-party URL.
+///
+/// URLs without a host, such as `data:` URLs, are never same-site.
+pub fn same_site(request: &Url, first_party: &Url) -> bool {
+    match (Site::from_url(request), Site::from_url(first_party)) {
+        (Some(request), Some(first_party)) => request == first_party,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn site(host: &str) -> String {
+        Site::from_host(&Host::parse(host).unwrap()).to_string()
+    }
+
+    #[test]
+    fn registrable_domains() {
+        assert_eq!(site("example.com"), "example.com");
+        assert_eq!(site("www.example.com"), "example.com");
+        assert_eq!(site("a.b.example.co.uk"), "example.co.uk");
+        assert_eq!(site("user.github.io"), "user.github.io");
+        assert_eq!(site("co.uk"), "co.uk");
+        assert_eq!(site("localhost"), "localhost");
+        assert_eq!(site("www.example.com."), "example.com");
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(site("192.168.0.1"), "192.168.0.1");
+        assert_eq!(site("[::1]"), "[::1]");
+        assert_ne!(site("192.168.0.1"), site("192.168.0.2"));
+    }
+
+    #[test]
+    fn same_site_requests() {
+        let same = |request: &str, first_party: &str| {
+            same_site(&request.parse().unwrap(), &first_party.parse().unwrap())
+        };
+
+        assert!(same("https://api.example.com/data", "https://www.example.com/"));
+        assert!(same("http://example.com/", "https://www.example.com/"));
+        assert!(!same("https://example.org/", "https://example.com/"));
+        assert!(!same("https://a.github.io/", "https://b.github.io/"));
+        assert!(same("http://127.0.0.1:8080/", "http://127.0.0.1/"));
+        assert!(!same("http://127.0.0.1/", "http://localhost/"));
+        assert!(!same("data:text/plain,hello", "https://example.com/"));
+    }
+}
@@ -0,0 +1,240 @@
+//! Checks of the behaviour expected of every cookie store.
+//!
+//! Implementations of [`CookieStore`](../trait.CookieStore.html) should call
+//! [`run`](fn.run.html) from a test:
+//!
+//! ```
+//! # use cookie_jar::store::{conformance, TreeStore};
+//! conformance::run(TreeStore::new);
+//! ```
+
+use std::borrow::Cow;
+use std::time::{Duration, UNIX_EPOCH};
+
+use url::{Host, Url};
+
+use ::cookie::{Builder, Cookie};
+use ::site::borrow_host;
+use super::CookieStore;
+
+/// Check that a store behaves as expected, panicking if it does not.
+///
+/// A new empty store is created for each check.
+pub fn run<S, F>(new_store: F)
+where
+    S: CookieStore,
+    F: Fn() -> S,
+{
+    insert_and_iterate(new_store());
+    replace_same_identity(new_store());
+    match_domains(new_store());
+    match_paths(new_store());
+    match_addresses(new_store());
+    isolate_partitions(new_store());
+    remove(new_store());
+    purge(new_store());
+}
+
+/// Build a cookie for a host.
+fn host_cookie(host: &str, path: &str, pair: &str) -> Cookie {
+    Builder::new()
+        .host_str(host)
+        .path(path)
+        .pair_str(pair)
+        .build_cookie()
+        .expect("Could not build cookie")
+}
+
+/// Build a cookie for a domain and its subdomains.
+fn domain_cookie(domain: &str, path: &str, pair: &str) -> Cookie {
+    Builder::new()
+        .domain(domain)
+        .path(path)
+        .pair_str(pair)
+        .build_cookie()
+        .expect("Could not build cookie")
+}
+
+/// Get the sorted names of the cookies in a partition that match a URL.
+fn matching_names<S: CookieStore>(store: &S, url: &str, partition: Option<&Host>) -> Vec<String> {
+    let url: Url = url.parse().expect("Could not parse URL");
+    let mut names: Vec<_> = store
+        .matches(&url, partition.map(borrow_host))
+        .map(|cookie| cookie.pair().name().to_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Get the sorted names of all of the cookies in the store.
+fn all_names<S: CookieStore>(store: &S) -> Vec<String> {
+    let mut names: Vec<_> = store
+        .iter()
+        .map(|(_, cookie)| cookie.pair().name().to_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Inserted cookies are listed by iteration.
+fn insert_and_iterate<S: CookieStore>(mut store: S) {
+    assert_eq!(all_names(&store), Vec::<String>::new());
+
+    assert_eq!(store.insert(host_cookie("example.com", "/", "a=1"), None), None);
+    assert_eq!(store.insert(domain_cookie("example.com", "/path", "b=1"), None), None);
+    assert_eq!(store.insert(host_cookie("127.0.0.1", "/", "c=1"), None), None);
+
+    assert_eq!(all_names(&store), ["a", "b", "c"]);
+}
+
+/// Cookies with the same host, path and name replace each other.
+fn replace_same_identity<S: CookieStore>(mut store: S) {
+    let original = host_cookie("example.com", "/", "a=1");
+    store.insert(host_cookie("example.com", "/", "a=1"), None);
+
+    let replaced = store.insert(host_cookie("example.com", "/", "a=2"), None);
+    assert_eq!(replaced, Some(original));
+
+    // Differing in any part of the identity is a different cookie.
+    assert_eq!(store.insert(host_cookie("www.example.com", "/", "a=3"), None), None);
+    assert_eq!(store.insert(host_cookie("example.com", "/other", "a=4"), None), None);
+    assert_eq!(store.insert(host_cookie("example.com", "/", "b=5"), None), None);
+
+    let mut pairs: Vec<_> = store
+        .iter()
+        .map(|(_, cookie)| cookie.pair().as_str().to_owned())
+        .collect();
+    pairs.sort();
+    assert_eq!(pairs, ["a=2", "a=3", "a=4", "b=5"]);
+}
+
+/// Host-only cookies match their host and domain cookies also match subdomains.
+fn match_domains<S: CookieStore>(mut store: S) {
+    store.insert(host_cookie("example.com", "/", "host=1"), None);
+    store.insert(domain_cookie("example.com", "/", "domain=1"), None);
+    store.insert(host_cookie("www.example.com", "/", "www=1"), None);
+    store.insert(host_cookie("other.com", "/", "other=1"), None);
+
+    assert_eq!(matching_names(&store, "http://example.com/", None), ["domain", "host"]);
+    assert_eq!(matching_names(&store, "http://www.example.com/", None), ["domain", "www"]);
+    assert_eq!(matching_names(&store, "http://a.b.example.com/", None), ["domain"]);
+    assert_eq!(matching_names(&store, "http://notexample.com/", None), Vec::<String>::new());
+    assert_eq!(matching_names(&store, "http://com/", None), Vec::<String>::new());
+}
+
+/// Cookies match requests for their path and its sub-paths.
+fn match_paths<S: CookieStore>(mut store: S) {
+    store.insert(host_cookie("example.com", "/", "root=1"), None);
+    store.insert(host_cookie("example.com", "/account", "account=1"), None);
+    store.insert(host_cookie("example.com", "/account/settings/", "settings=1"), None);
+
+    assert_eq!(matching_names(&store, "http://example.com/", None), ["root"]);
+    assert_eq!(
+        matching_names(&store, "http://example.com/account", None),
+        ["account", "root"]
+    );
+    assert_eq!(
+        matching_names(&store, "http://example.com/account/settings/page", None),
+        ["account", "root", "settings"]
+    );
+    assert_eq!(matching_names(&store, "http://example.com/accounts", None), ["root"]);
+}
+
+/// Cookies for IP addresses only match that exact address.
+fn match_addresses<S: CookieStore>(mut store: S) {
+    store.insert(host_cookie("127.0.0.1", "/", "v4=1"), None);
+    store.insert(host_cookie("[::1]", "/", "v6=1"), None);
+
+    assert_eq!(matching_names(&store, "http://127.0.0.1/", None), ["v4"]);
+    assert_eq!(matching_names(&store, "http://[::1]/", None), ["v6"]);
+    assert_eq!(matching_names(&store, "http://127.0.0.2/", None), Vec::<String>::new());
+}
+
+/// Cookies in one partition are not matched for another.
+fn isolate_partitions<S: CookieStore>(mut store: S) {
+    let first = Host::parse("first.example").unwrap();
+    let second = Host::parse("second.example").unwrap();
+
+    store.insert(host_cookie("example.com", "/", "none=1"), None);
+    store.insert(host_cookie("example.com", "/", "first=1"), Some(first.clone()));
+    store.insert(host_cookie("example.com", "/", "second=1"), Some(second.clone()));
+
+    let url = "http://example.com/";
+    assert_eq!(matching_names(&store, url, None), ["none"]);
+    assert_eq!(matching_names(&store, url, Some(&first)), ["first"]);
+    assert_eq!(matching_names(&store, url, Some(&second)), ["second"]);
+
+    let third = Host::parse("third.example").unwrap();
+    assert_eq!(matching_names(&store, url, Some(&third)), Vec::<String>::new());
+
+    let mut partitions: Vec<_> = store
+        .iter()
+        .map(|(partition, cookie)| {
+            (partition.map(Cow::into_owned), cookie.pair().name().to_owned())
+        })
+        .collect();
+    partitions.sort();
+    assert_eq!(
+        partitions,
+        [
+            (None, "none".to_owned()),
+            (Some(first), "first".to_owned()),
+            (Some(second), "second".to_owned()),
+        ]
+    );
+}
+
+/// Removed cookies are returned and no longer stored.
+fn remove<S: CookieStore>(mut store: S) {
+    let partition = Host::parse("first.example").unwrap();
+    let host = Host::parse("example.com").unwrap();
+
+    store.insert(host_cookie("example.com", "/path", "a=1"), None);
+    store.insert(host_cookie("example.com", "/path", "b=1"), Some(partition.clone()));
+
+    assert_eq!(store.remove(&host, "/path", "a", Some(&partition)), None);
+    assert_eq!(store.remove(&host, "/", "a", None), None);
+    assert_eq!(store.remove(&host, "/path", "missing", None), None);
+
+    let removed = store.remove(&host, "/path", "a", None);
+    assert_eq!(removed, Some(host_cookie("example.com", "/path", "a=1")));
+    assert_eq!(store.remove(&host, "/path", "a", None), None);
+
+    let removed = store.remove(&host, "/path", "b", Some(&partition));
+    assert_eq!(removed, Some(host_cookie("example.com", "/path", "b=1")));
+
+    assert_eq!(all_names(&store), Vec::<String>::new());
+    assert_eq!(
+        matching_names(&store, "http://example.com/path", None),
+        Vec::<String>::new()
+    );
+}
+
+/// Purging removes and returns exactly the cookies that have expired.
+fn purge<S: CookieStore>(mut store: S) {
+    let partition = Host::parse("first.example").unwrap();
+    let expiring = |pair: &str, seconds: u64| {
+        Builder::new()
+            .host_str("example.com")
+            .path("/")
+            .pair_str(pair)
+            .expiry(UNIX_EPOCH + Duration::from_secs(seconds))
+            .build_cookie()
+            .expect("Could not build cookie")
+    };
+
+    store.insert(expiring("past=1", 1_000), None);
+    store.insert(expiring("now=1", 2_000), Some(partition));
+    store.insert(expiring("future=1", 3_000), None);
+    store.insert(host_cookie("example.com", "/", "session=1"), None);
+
+    let mut purged: Vec<_> = store
+        .purge(UNIX_EPOCH + Duration::from_secs(2_000))
+        .iter()
+        .map(|cookie| cookie.pair().name().to_owned())
+        .collect();
+    purged.sort();
+
+    assert_eq!(purged, ["now", "past"]);
+    assert_eq!(all_names(&store), ["future", "session"]);
+}
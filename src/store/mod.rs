@@ -0,0 +1,210 @@
+//! Storage backends for a cookie jar.
+//!
+//! A [`Jar`](../struct.Jar.html) keeps its cookies in a [`CookieStore`](trait.CookieStore.html).
+//! The default [`TreeStore`](struct.TreeStore.html) holds them in memory, while other
+//! implementations may keep them on disk or share them between jars.
+//!
+//! A store lends out the cookies it holds in memory and hands out copies of those it cannot
+//! borrow from, such as cookies held behind a lock or on disk.
+//!
+//! Cookies are identified by their host, path and name within a partition. A partition is keyed
+//! by the [site](../site/index.html) of the top-level page a partitioned cookie was set from; all
+//! other cookies are stored in the unpartitioned set with a key of `None`.
+//!
+//! Implementations can be checked against the expected behaviour using
+//! [`conformance::run`](conformance/fn.run.html).
+
+pub mod conformance;
+mod tree;
+
+use std::borrow::Cow;
+use std::time::SystemTime;
+
+use url::{Host, Url};
+
+use ::cookie::Cookie;
+
+pub use self::tree::{TreeMatches, TreeStore};
+
+/// All of the cookies in a store along with the key of the partition each is in.
+pub type StoredCookies<'s> =
+    Box<dyn Iterator<Item = (Option<Cow<'s, Host>>, Cow<'s, Cookie>)> + 's>;
+
+/// Storage for the cookies held by a jar.
+pub trait CookieStore {
+    /// Add a cookie to a partition.
+    ///
+    /// Returns the cookie with the same host, path and name that was replaced, if any.
+    fn insert(&mut self, cookie: Cookie, partition: Option<Host>) -> Option<Cookie>;
+
+    /// Remove the cookie with a given host, path and name from a partition.
+    fn remove(&mut self, host: &Host, path: &str, name: &str, partition: Option<&Host>)
+        -> Option<Cookie>;
+
+    /// The iterator over the cookies that match a URL.
+    type Matches<'s>: Iterator<Item = Cow<'s, Cookie>>
+    where
+        Self: 's;
+
+    /// Get the cookies in a single partition whose host and path match a URL.
+    ///
+    /// Cookies that are not host-only also match subdomains of their host. This is called for
+    /// every request so stores that can lend out their cookies should borrow them rather than
+    /// copy them. The key of the partition is borrowed from the URL of the top-level page.
+    fn matches<'s>(&'s self, url: &'s Url, partition: Option<Host<&str>>) -> Self::Matches<'s>;
+
+    /// Get all of the cookies in the store along with the partition they are in.
+    fn iter<'s>(&'s self) -> StoredCookies<'s>;
+
+    /// Remove all of the cookies that had expired by a given time.
+    fn purge(&mut self, now: SystemTime) -> Vec<Cookie>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::vec;
+
+    /// A store that checks every cookie in turn.
+    #[derive(Default)]
+    struct ListStore {
+        cookies: Vec<(Option<Host>, Cookie)>,
+    }
+
+    impl ListStore {
+        /// Find the position of a cookie by its identity.
+        fn position(&self, host: &Host, path: &str, name: &str, partition: Option<&Host>)
+            -> Option<usize>
+        {
+            self.cookies.iter().position(|(key, cookie)| {
+                key.as_ref() == partition && cookie.host() == host && cookie.path() == path
+                    && cookie.pair().name() == name
+            })
+        }
+    }
+
+    /// Split a path into its non-empty segments.
+    fn segments(path: &str) -> Vec<&str> {
+        path.split('/').filter(|segment| !segment.is_empty()).collect()
+    }
+
+    impl CookieStore for ListStore {
+        fn insert(&mut self, cookie: Cookie, partition: Option<Host>) -> Option<Cookie> {
+            let position = self.position(
+                cookie.host(),
+                cookie.path(),
+                cookie.pair().name(),
+                partition.as_ref(),
+            );
+            let replaced = position.map(|position| self.cookies.remove(position).1);
+            self.cookies.push((partition, cookie));
+            replaced
+        }
+
+        fn remove(&mut self, host: &Host, path: &str, name: &str, partition: Option<&Host>)
+            -> Option<Cookie>
+        {
+            self.position(host, path, name, partition)
+                .map(|position| self.cookies.remove(position).1)
+        }
+
+        type Matches<'s> = Box<dyn Iterator<Item = Cow<'s, Cookie>> + 's>;
+
+        fn matches<'s>(&'s self, url: &'s Url, partition: Option<Host<&str>>)
+            -> Box<dyn Iterator<Item = Cow<'s, Cookie>> + 's>
+        {
+            let partition = partition.map(|key| key.to_owned());
+            Box::new(self.cookies.iter().filter(move |(key, cookie)| {
+                let host = match (url.host(), cookie.host()) {
+                    (Some(Host::Domain(url)), Host::Domain(domain)) => {
+                        url == domain
+                            || (!cookie.host_only() && url.ends_with(&format!(".{}", domain)))
+                    }
+                    (Some(url), host) => url.to_owned() == *host,
+                    (None, _) => false,
+                };
+                let path = segments(url.path()).starts_with(&segments(cookie.path()));
+                *key == partition && host && path
+            }).map(|(_, cookie)| Cow::Borrowed(cookie)))
+        }
+
+        fn iter<'s>(&'s self) -> StoredCookies<'s> {
+            Box::new(self.cookies.iter().map(|(key, cookie)| {
+                (key.as_ref().map(Cow::Borrowed), Cow::Borrowed(cookie))
+            }))
+        }
+
+        fn purge(&mut self, now: SystemTime) -> Vec<Cookie> {
+            let (expired, kept) = self.cookies
+                .drain(..)
+                .partition(|(_, cookie)| cookie.expired_since(now));
+            self.cookies = kept;
+            expired.into_iter().map(|(_, cookie)| cookie).collect()
+        }
+    }
+
+    /// A store shared between jars behind a lock, which hands out copies of its cookies.
+    #[derive(Default, Clone)]
+    struct SharedStore(Arc<Mutex<TreeStore>>);
+
+    impl CookieStore for SharedStore {
+        fn insert(&mut self, cookie: Cookie, partition: Option<Host>) -> Option<Cookie> {
+            self.0.lock().unwrap().insert(cookie, partition)
+        }
+
+        fn remove(&mut self, host: &Host, path: &str, name: &str, partition: Option<&Host>)
+            -> Option<Cookie>
+        {
+            self.0.lock().unwrap().remove(host, path, name, partition)
+        }
+
+        type Matches<'s> = vec::IntoIter<Cow<'s, Cookie>>;
+
+        fn matches<'s>(&'s self, url: &'s Url, partition: Option<Host<&str>>)
+            -> vec::IntoIter<Cow<'s, Cookie>>
+        {
+            let store = self.0.lock().unwrap();
+            let matches: Vec<_> = store
+                .matches(url, partition)
+                .map(|cookie| Cow::Owned(cookie.into_owned()))
+                .collect();
+            matches.into_iter()
+        }
+
+        fn iter<'s>(&'s self) -> StoredCookies<'s> {
+            let store = self.0.lock().unwrap();
+            let cookies: Vec<_> = store
+                .iter()
+                .map(|(key, cookie)| {
+                    (key.map(|key| Cow::Owned(key.into_owned())), Cow::Owned(cookie.into_owned()))
+                })
+                .collect();
+            Box::new(cookies.into_iter())
+        }
+
+        fn purge(&mut self, now: SystemTime) -> Vec<Cookie> {
+            self.0.lock().unwrap().purge(now)
+        }
+    }
+
+    #[test]
+    fn conformance() {
+        conformance::run(ListStore::default);
+        conformance::run(SharedStore::default);
+    }
+
+    #[test]
+    fn shared_between_jars() {
+        use jar::{ClockFn, Jar};
+
+        let store = SharedStore::default();
+        let now = SystemTime::now as ClockFn;
+        let mut first = Jar::with_store(now, store.clone());
+        let second = Jar::with_store(now, store);
+
+        let url: Url = "https://example.com/".parse().unwrap();
+        first.add_cookie(Cookie::parse("SID=1", &url).unwrap(), None);
+        assert_eq!(second.cookie_header(&url, None), Some("SID=1".to_owned()));
+    }
+}
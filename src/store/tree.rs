@@ -0,0 +1,477 @@
+//! The default in-memory cookie store.
+//!
+//! The store is structured as a tree representing the domains the the paths for which it has
+//! stored values. The store contains a root domain which branches out into its
+//! subdomains. Similarly, each domain contains its root path which branches out into its
+//! sub-paths.
+
+use std::borrow::Cow;
+use std::collections::hash_map::{self, HashMap};
+use std::iter::{Filter, Peekable};
+use std::net::IpAddr;
+use std::str::{RSplit, Split};
+use std::time::SystemTime;
+
+use url::{Host, Url};
+
+use ::cookie::Cookie;
+use super::{CookieStore, StoredCookies};
+
+/// A cookie store held in memory as a tree of domains and paths.
+#[derive(Debug, Default)]
+pub struct TreeStore {
+    cookies: Partition,
+    /// The partitions for domain sites, keyed by domain so that they can be found from a borrowed
+    /// host, along with the key each was inserted with.
+    domains: HashMap<String, (Host, Partition)>,
+    /// The partitions for IP address sites along with the key each was inserted with.
+    addresses: HashMap<IpAddr, (Host, Partition)>,
+}
+
+impl TreeStore {
+    /// Create a new empty store.
+    pub fn new() -> TreeStore {
+        TreeStore::default()
+    }
+
+    /// Get the partition with a given key.
+    fn partition(&self, partition: Option<Host<&str>>) -> Option<&Partition> {
+        let keyed = match partition {
+            Some(Host::Domain(domain)) => self.domains.get(domain),
+            Some(Host::Ipv4(addr)) => self.addresses.get(&IpAddr::V4(addr)),
+            Some(Host::Ipv6(addr)) => self.addresses.get(&IpAddr::V6(addr)),
+            None => return Some(&self.cookies),
+        };
+        keyed.map(|(_, partition)| partition)
+    }
+
+    /// Get the partition with a given key for modification.
+    fn partition_mut(&mut self, partition: Option<&Host>) -> Option<&mut Partition> {
+        let keyed = match partition {
+            Some(Host::Domain(domain)) => self.domains.get_mut(domain),
+            Some(&Host::Ipv4(addr)) => self.addresses.get_mut(&IpAddr::V4(addr)),
+            Some(&Host::Ipv6(addr)) => self.addresses.get_mut(&IpAddr::V6(addr)),
+            None => return Some(&mut self.cookies),
+        };
+        keyed.map(|(_, partition)| partition)
+    }
+}
+
+impl CookieStore for TreeStore {
+    fn insert(&mut self, cookie: Cookie, partition: Option<Host>) -> Option<Cookie> {
+        let keyed = match partition {
+            Some(Host::Domain(domain)) => self.domains
+                .entry(domain.clone())
+                .or_insert_with(|| (Host::Domain(domain), Partition::default())),
+            Some(Host::Ipv4(addr)) => self.addresses
+                .entry(IpAddr::V4(addr))
+                .or_insert_with(|| (Host::Ipv4(addr), Partition::default())),
+            Some(Host::Ipv6(addr)) => self.addresses
+                .entry(IpAddr::V6(addr))
+                .or_insert_with(|| (Host::Ipv6(addr), Partition::default())),
+            None => return self.cookies.insert(cookie),
+        };
+
+        keyed.1.insert(cookie)
+    }
+
+    fn remove(&mut self, host: &Host, path: &str, name: &str, partition: Option<&Host>)
+        -> Option<Cookie>
+    {
+        self.partition_mut(partition)
+            .and_then(|partition| partition.remove(host, path, name))
+    }
+
+    type Matches<'s> = TreeMatches<'s>;
+
+    fn matches<'s>(&'s self, url: &'s Url, partition: Option<Host<&str>>) -> TreeMatches<'s> {
+        match self.partition(partition) {
+            Some(partition) => partition.url_matches(url),
+            None => TreeMatches::empty(url.path()),
+        }
+    }
+
+    fn iter<'s>(&'s self) -> StoredCookies<'s> {
+        let unpartitioned = self.cookies.cookies().map(|cookie| (None, Cow::Borrowed(cookie)));
+        let keyed = self.domains.values().chain(self.addresses.values());
+        let partitioned = keyed.flat_map(|(key, partition)| {
+            partition
+                .cookies()
+                .map(move |cookie| (Some(Cow::Borrowed(key)), Cow::Borrowed(cookie)))
+        });
+
+        Box::new(unpartitioned.chain(partitioned))
+    }
+
+    fn purge(&mut self, now: SystemTime) -> Vec<Cookie> {
+        let mut purged = Vec::new();
+        self.cookies.purge(now, &mut purged);
+        let keyed = self.domains.values_mut().chain(self.addresses.values_mut());
+        for (_, partition) in keyed {
+            partition.purge(now, &mut purged);
+        }
+        purged
+    }
+}
+
+/// The non-empty segments of a path.
+type PathSegments<'s> = Filter<Split<'s, char>, fn(&&'s str) -> bool>;
+
+/// The labels of a domain from the least specific to the most.
+type DomainSegments<'s> = RSplit<'s, char>;
+
+/// Split a path into its non-empty segments.
+fn path_segments<'s>(path: &'s str) -> PathSegments<'s> {
+    path.split('/').filter(non_empty)
+}
+
+/// Check if a segment of a path is not empty.
+fn non_empty(segment: &&str) -> bool {
+    !segment.is_empty()
+}
+
+/// Split a domain into its labels from the least specific to the most.
+fn domain_segments<'s>(domain: &'s str) -> DomainSegments<'s> {
+    domain.trim_matches('.').rsplit('.')
+}
+
+/// The cookies stored for a single partition of the store.
+#[derive(Debug, Default)]
+struct Partition {
+    domain: Domain,
+    hosts: HashMap<IpAddr, Path>,
+}
+
+impl Partition {
+    /// Add a cookie to the partition.
+    fn insert(&mut self, cookie: Cookie) -> Option<Cookie> {
+        let host = cookie.host().clone();
+        let path = cookie.path().to_owned();
+        let path_segments = path_segments(&path);
+        match host {
+            Host::Domain(domain) => {
+                self.domain.insert(domain_segments(&domain), path_segments, cookie)
+            }
+            Host::Ipv4(addr) => self.update_host(IpAddr::V4(addr), path_segments, cookie),
+            Host::Ipv6(addr) => self.update_host(IpAddr::V6(addr), path_segments, cookie),
+        }
+    }
+
+    /// Update a cookie for a host.
+    fn update_host<'s, S>(&mut self, host: IpAddr, segments: S, cookie: Cookie) -> Option<Cookie>
+    where
+        S: Iterator<Item = &'s str> + 's,
+    {
+        self.hosts.entry(host)
+            .or_insert_with(Path::default)
+            .insert(segments, cookie)
+    }
+
+    /// Remove the cookie with a given host, path and name.
+    fn remove(&mut self, host: &Host, path: &str, name: &str) -> Option<Cookie> {
+        let path_segments = path_segments(path);
+        match *host {
+            Host::Domain(ref domain) => {
+                self.domain.remove(domain_segments(domain), path_segments, name)
+            }
+            Host::Ipv4(addr) => self.hosts
+                .get_mut(&IpAddr::V4(addr))
+                .and_then(|host| host.remove(path_segments, name)),
+            Host::Ipv6(addr) => self.hosts
+                .get_mut(&IpAddr::V6(addr))
+                .and_then(|host| host.remove(path_segments, name)),
+        }
+    }
+
+    /// Get the matching cookies for a Url.
+    fn url_matches<'j>(&'j self, url: &'j Url) -> TreeMatches<'j> {
+        match url.host() {
+            Some(Host::Domain(domain)) => TreeMatches::domain(&self.domain, domain, url.path()),
+            Some(Host::Ipv4(addr)) => self.host_matches(IpAddr::V4(addr), url.path()),
+            Some(Host::Ipv6(addr)) => self.host_matches(IpAddr::V6(addr), url.path()),
+            None => TreeMatches::empty(url.path()),
+        }
+    }
+
+    /// Get all of the matches for a specific host.
+    fn host_matches<'j>(&'j self, host: IpAddr, path: &'j str) -> TreeMatches<'j> {
+        TreeMatches {
+            labels: domain_segments("").peekable(),
+            domain: None,
+            path,
+            cookies: PathMatches::new(self.hosts.get(&host), path, HostMatch::Exact),
+        }
+    }
+
+    /// Get all of the cookies in the partition.
+    fn cookies(&self) -> impl Iterator<Item = &Cookie> {
+        self.domain.cookies().chain(self.hosts.values().flat_map(Path::cookies))
+    }
+
+    /// Remove all of the cookies that have expired.
+    fn purge(&mut self, now: SystemTime, purged: &mut Vec<Cookie>) {
+        self.domain.purge(now, purged);
+        for host in self.hosts.values_mut() {
+            host.purge(now, purged);
+        }
+    }
+}
+
+/// The given URL is an exact host match.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum HostMatch {
+    Exact,
+    Suffix,
+}
+
+/// The heirarchy of domains.
+#[derive(Debug, Default)]
+struct Domain {
+    path: Path,
+    children: HashMap<String, Domain>,
+}
+
+impl Domain {
+    /// Add a cookie to a domain.
+    pub fn insert<'p, P>(&mut self, mut segments: DomainSegments, path: P, cookie: Cookie)
+        -> Option<Cookie>
+    where
+        P: Iterator<Item = &'p str> + 'p,
+    {
+        if let Some(child) = segments.next() {
+            self.children.entry(child.to_owned())
+                .or_insert_with(Domain::default)
+                .insert(segments, path, cookie)
+        } else {
+            self.path.insert(path, cookie)
+        }
+    }
+
+    /// Remove a cookie from a domain.
+    pub fn remove<'p, P>(&mut self, mut segments: DomainSegments, path: P, name: &str)
+        -> Option<Cookie>
+    where
+        P: Iterator<Item = &'p str> + 'p,
+    {
+        if let Some(child) = segments.next() {
+            self.children
+                .get_mut(child)
+                .and_then(|child| child.remove(segments, path, name))
+        } else {
+            self.path.remove(path, name)
+        }
+    }
+
+    /// Get all of the cookies for the domain and its subdomains.
+    pub fn cookies<'c>(&'c self) -> Box<Iterator<Item = &'c Cookie> + 'c> {
+        Box::new(
+            self.path
+                .cookies()
+                .chain(self.children.values().flat_map(Domain::cookies)),
+        )
+    }
+
+    /// Remove all of the cookies for the domain and its subdomains that have expired.
+    pub fn purge(&mut self, now: SystemTime, purged: &mut Vec<Cookie>) {
+        self.path.purge(now, purged);
+        for child in self.children.values_mut() {
+            child.purge(now, purged);
+        }
+    }
+}
+
+/// The heriarchy of paths.
+#[derive(Debug, Default)]
+struct Path {
+    cookies: HashMap<String, Cookie>,
+    children: HashMap<String, Path>,
+}
+
+impl Path {
+    /// Add a cookie to the matching path.
+    pub fn insert<'s, S>(&mut self, mut segments: S, cookie: Cookie) -> Option<Cookie>
+    where
+        S: Iterator<Item = &'s str> + 's,
+    {
+        if let Some(child) = segments.next() {
+            self.children
+                .entry(child.to_owned())
+                .or_insert_with(Path::default)
+                .insert(segments, cookie)
+        } else {
+            self.cookies.insert(cookie.pair().name().to_owned(), cookie)
+        }
+    }
+
+    /// Remove a cookie from the matching path.
+    pub fn remove<'s, S>(&mut self, mut segments: S, name: &str) -> Option<Cookie>
+    where
+        S: Iterator<Item = &'s str> + 's,
+    {
+        if let Some(child) = segments.next() {
+            self.children
+                .get_mut(child)
+                .and_then(|child| child.remove(segments, name))
+        } else {
+            self.cookies.remove(name)
+        }
+    }
+
+    /// Get all of the cookies for the path and its sub-paths.
+    pub fn cookies<'c>(&'c self) -> Box<Iterator<Item = &'c Cookie> + 'c> {
+        Box::new(
+            self.cookies
+                .values()
+                .chain(self.children.values().flat_map(Path::cookies)),
+        )
+    }
+
+    /// Remove all of the cookies for the path and its sub-paths that have expired.
+    pub fn purge(&mut self, now: SystemTime, purged: &mut Vec<Cookie>) {
+        let expired: Vec<_> = self.cookies
+            .iter()
+            .filter(|&(_, cookie)| cookie.expired_since(now))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for name in expired {
+            purged.extend(self.cookies.remove(&name));
+        }
+
+        for child in self.children.values_mut() {
+            child.purge(now, purged);
+        }
+    }
+}
+
+/// The cookies of a partition that match a URL.
+///
+/// Matches are found by walking down the tree of domains from the least specific label of the
+/// host and, within each domain, down the tree of paths along the segments of the URL path. The
+/// walk holds only its position in each tree and lends out the cookies it finds, so no allocation
+/// is made.
+#[derive(Debug)]
+pub struct TreeMatches<'s> {
+    /// The labels of the host below the current domain.
+    labels: Peekable<DomainSegments<'s>>,
+    /// The domain whose paths are being walked, None once there are no more domains to walk.
+    domain: Option<&'s Domain>,
+    /// The path of the URL.
+    path: &'s str,
+    /// The matching cookies of the current domain.
+    cookies: PathMatches<'s>,
+}
+
+impl<'s> TreeMatches<'s> {
+    /// Walk the domains that a host is a subdomain of, starting at the root.
+    fn domain(root: &'s Domain, host: &'s str, path: &'s str) -> TreeMatches<'s> {
+        let mut labels = domain_segments(host).peekable();
+        let cookies = PathMatches::new(Some(&root.path), path, HostMatch::remaining(&mut labels));
+        TreeMatches {
+            labels,
+            domain: Some(root),
+            path,
+            cookies,
+        }
+    }
+
+    /// Match no cookies.
+    fn empty(path: &'s str) -> TreeMatches<'s> {
+        TreeMatches {
+            labels: domain_segments("").peekable(),
+            domain: None,
+            path,
+            cookies: PathMatches::new(None, path, HostMatch::Exact),
+        }
+    }
+}
+
+impl<'s> Iterator for TreeMatches<'s> {
+    type Item = Cow<'s, Cookie>;
+
+    fn next(&mut self) -> Option<Cow<'s, Cookie>> {
+        loop {
+            if let Some(cookie) = self.cookies.next() {
+                return Some(Cow::Borrowed(cookie));
+            }
+
+            // Descend to the subdomain for the next label of the host.
+            let domain = self.domain.take()?;
+            let label = self.labels.next()?;
+            let child = domain.children.get(label)?;
+            let host = HostMatch::remaining(&mut self.labels);
+            self.domain = Some(child);
+            self.cookies = PathMatches::new(Some(&child.path), self.path, host);
+        }
+    }
+}
+
+impl HostMatch {
+    /// Get how cookies of a domain match a host with some labels below that domain.
+    fn remaining(labels: &mut Peekable<DomainSegments>) -> HostMatch {
+        if labels.peek().is_some() {
+            HostMatch::Suffix
+        } else {
+            HostMatch::Exact
+        }
+    }
+}
+
+/// The cookies of a domain that match the path of a URL.
+#[derive(Debug)]
+struct PathMatches<'s> {
+    /// The segments of the URL path below the current path.
+    segments: PathSegments<'s>,
+    /// The path whose cookies are being checked, None once there are no more paths to check.
+    node: Option<&'s Path>,
+    /// The cookies of the current path that remain to be checked.
+    cookies: Option<hash_map::Values<'s, String, Cookie>>,
+    /// How the host of the URL matches the domain.
+    host: HostMatch,
+}
+
+impl<'s> PathMatches<'s> {
+    /// Walk the paths beneath a root path along the segments of a URL path.
+    fn new(root: Option<&'s Path>, path: &'s str, host: HostMatch) -> PathMatches<'s> {
+        PathMatches {
+            segments: path_segments(path),
+            node: root,
+            cookies: root.map(|root| root.cookies.values()),
+            host,
+        }
+    }
+}
+
+impl<'s> Iterator for PathMatches<'s> {
+    type Item = &'s Cookie;
+
+    fn next(&mut self) -> Option<&'s Cookie> {
+        loop {
+            let host = self.host;
+            let found = self.cookies.as_mut()?.find(|cookie| match host {
+                HostMatch::Exact => true,
+                HostMatch::Suffix => !cookie.host_only(),
+            });
+            if found.is_some() {
+                return found;
+            }
+
+            // Descend to the sub-path for the next segment of the URL path.
+            let node = self.node.take()?;
+            let child = self.segments.next().and_then(|segment| node.children.get(segment));
+            self.node = child;
+            self.cookies = child.map(|child| child.cookies.values());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use store::conformance;
+
+    #[test]
+    fn conformance() {
+        conformance::run(TreeStore::new);
+    }
+}
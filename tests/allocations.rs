@@ -0,0 +1,91 @@
+//! Checks that matching the cookies of a jar against a URL does not allocate.
+//!
+//! Allocations are counted by a global allocator, so this file holds a single test to keep other
+//! tests from allocating while it runs.
+
+extern crate cookie_jar;
+extern crate url;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use cookie_jar::{ClockFn, Cookie, Jar};
+use cookie_jar::policy::{BlockDomains, BlockThirdParty, Domains, Policies};
+use url::Url;
+
+/// The system allocator, counting the allocations made.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Count the allocations made by a function.
+fn allocations<F: FnOnce() -> usize>(f: F) -> (usize, usize) {
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let result = f();
+    (ALLOCATIONS.load(Ordering::SeqCst) - before, result)
+}
+
+/// Check the number of cookies matched for URLs and that no allocation is made while matching.
+fn check(jar: &Jar<ClockFn>, checks: &[(&str, Option<&str>, usize)]) {
+    for &(url, top_level_site, expected) in checks {
+        let url: Url = url.parse().unwrap();
+        let top_level_site: Option<Url> = top_level_site.map(|site| site.parse().unwrap());
+        let (allocated, matched) =
+            allocations(|| jar.url_matches(&url, top_level_site.as_ref()).count());
+        assert_eq!(matched, expected, "{}", url);
+        assert_eq!(allocated, 0, "{} allocated while matching", url);
+    }
+}
+
+#[test]
+fn url_matches() {
+    let mut jar: Jar<ClockFn> = Jar::default();
+    let set_cookies = [
+        ("https://example.com/", "root=1; Domain=example.com; Path=/"),
+        ("https://example.com/", "host=1; Path=/"),
+        ("https://www.example.com/docs/", "docs=1; Domain=example.com"),
+        ("https://www.example.com/docs/", "page=1; Path=/docs/page"),
+        ("https://api.example.com/", "api=1"),
+        ("https://127.0.0.1/", "ip=1; Path=/docs"),
+    ];
+    for &(origin, set_cookie) in set_cookies.iter() {
+        let origin: Url = origin.parse().unwrap();
+        jar.add_cookie(Cookie::parse(set_cookie, &origin).unwrap(), None);
+    }
+    let origin: Url = "https://example.com/".parse().unwrap();
+    let partitioned = Cookie::parse("part=1; Secure; Path=/; Partitioned", &origin).unwrap();
+    jar.add_cookie(partitioned, Some(&"https://news.example/".parse().unwrap()));
+
+    check(&jar, &[
+        ("https://www.example.com/docs/page/index.html", None, 3),
+        ("https://example.com/", None, 2),
+        ("https://deep.www.example.com/docs", None, 2),
+        ("https://other.example/", None, 0),
+        ("https://127.0.0.1/docs/index.html", None, 1),
+        ("https://example.com/", Some("https://www.news.example/"), 3),
+        ("https://example.com/", Some("https://other.example/"), 2),
+    ]);
+
+    let blocked = Domains::parse(vec!["api.example.com"]).unwrap();
+    let jar = jar.policy(Policies::new().with(BlockDomains(blocked)).with(BlockThirdParty));
+    check(&jar, &[
+        ("https://www.example.com/docs/page/index.html", Some("https://example.com/"), 3),
+        ("https://api.example.com/", Some("https://www.example.com/"), 1),
+        ("https://example.com/", Some("https://news.example/"), 0),
+        ("https://127.0.0.1/docs/index.html", None, 1),
+    ]);
+}
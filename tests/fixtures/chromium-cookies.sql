@@ -0,0 +1,40 @@
+-- Source for chromium-cookies.sqlite.
+--
+-- Regenerate with: sqlite3 chromium-cookies.sqlite < chromium-cookies.sql
+CREATE TABLE cookies (
+    creation_utc INTEGER NOT NULL,
+    host_key TEXT NOT NULL,
+    top_frame_site_key TEXT NOT NULL,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    encrypted_value BLOB NOT NULL,
+    path TEXT NOT NULL,
+    expires_utc INTEGER NOT NULL,
+    is_secure INTEGER NOT NULL,
+    is_httponly INTEGER NOT NULL,
+    last_access_utc INTEGER NOT NULL,
+    has_expires INTEGER NOT NULL,
+    is_persistent INTEGER NOT NULL,
+    priority INTEGER NOT NULL,
+    samesite INTEGER NOT NULL,
+    source_scheme INTEGER NOT NULL,
+    source_port INTEGER NOT NULL,
+    last_update_utc INTEGER NOT NULL,
+    source_type INTEGER NOT NULL,
+    has_cross_site_ancestor INTEGER NOT NULL
+);
+
+-- Times are microseconds since 1601-01-01 00:00:00 UTC.
+INSERT INTO cookies VALUES
+    (13150473600000000, '.example.com', '', 'SID', '31d4d96e407aad42', X'', '/',
+        13644473600000000, 1, 1, 13250473600000000, 1, 1, 1, 1, 2, 443,
+        13250473600000000, 0, 0),
+    (13150473600000000, 'www.example.com', '', 'lang', 'en-US', X'', '/account',
+        0, 0, 0, 13250473600000000, 0, 0, 1, -1, 2, 443,
+        13250473600000000, 0, 0),
+    (13150473600000000, '127.0.0.1', '', 'local', '1', X'', '/',
+        13644473600000000, 0, 0, 13250473600000000, 1, 1, 1, 0, 1, 80,
+        13250473600000000, 0, 0),
+    (13150473600000000, 'www.example.com', '', 'secret', '', X'763130deadbeef', '/',
+        13644473600000000, 1, 1, 13250473600000000, 1, 1, 1, 2, 2, 443,
+        13250473600000000, 0, 0);
@@ -0,0 +1,34 @@
+-- Source for firefox-cookies.sqlite.
+--
+-- Regenerate with: sqlite3 firefox-cookies.sqlite < firefox-cookies.sql
+CREATE TABLE moz_cookies (
+    id INTEGER PRIMARY KEY,
+    originAttributes TEXT NOT NULL DEFAULT '',
+    name TEXT,
+    value TEXT,
+    host TEXT,
+    path TEXT,
+    expiry INTEGER,
+    lastAccessed INTEGER,
+    creationTime INTEGER,
+    isSecure INTEGER,
+    isHttpOnly INTEGER,
+    inBrowserElement INTEGER DEFAULT 0,
+    sameSite INTEGER DEFAULT 0,
+    rawSameSite INTEGER DEFAULT 0,
+    schemeMap INTEGER DEFAULT 0,
+    isPartitionedAttributeSet INTEGER DEFAULT 0,
+    CONSTRAINT moz_uniqueid UNIQUE (name, host, path, originAttributes)
+);
+
+INSERT INTO moz_cookies
+    (name, value, host, path, expiry, lastAccessed, creationTime, isSecure, isHttpOnly, sameSite)
+VALUES
+    ('SID', '31d4d96e407aad42', '.example.com', '/', 2000000000,
+        1600000000000000, 1500000000000000, 1, 1, 1),
+    ('lang', 'en-US', 'www.example.com', '/account', 2000000000,
+        1600000000000000, 1500000000000000, 0, 0, 2),
+    ('local', '1', '127.0.0.1', '/', 2000000000,
+        1600000000000000, 1500000000000000, 0, 0, 0),
+    ('bad name', '1', 'www.example.com', '/', 2000000000,
+        1600000000000000, 1500000000000000, 0, 0, 0);
@@ -0,0 +1,60 @@
+// Hand-written cookie-date cases, each the string to parse and the expected date in IMF-fixdate
+// form or None if the string must not parse as a date.
+&[
+    ("Mon, 10-Dec-2007 17:02:24 GMT", Some("Mon, 10 Dec 2007 17:02:24 GMT")),
+    ("Wed, 09 Dec 2009 16:27:23 GMT", Some("Wed, 09 Dec 2009 16:27:23 GMT")),
+    ("Thursday, 01-Jan-1970 00:00:00 GMT", Some("Thu, 01 Jan 1970 00:00:00 GMT")),
+    ("Thu, 01-Jan-1970 00:00:01 GMT", Some("Thu, 01 Jan 1970 00:00:01 GMT")),
+    ("Mon Dec 10 16:32:30 2007 GMT", Some("Mon, 10 Dec 2007 16:32:30 GMT")),
+    ("Wednesday, 01-Jan-10 00:00:00 GMT", Some("Fri, 01 Jan 2010 00:00:00 GMT")),
+    ("Sun, 06 Nov 1994 08:49:37 GMT", Some("Sun, 06 Nov 1994 08:49:37 GMT")),
+    ("Sunday, 06-Nov-94 08:49:37 GMT", Some("Sun, 06 Nov 1994 08:49:37 GMT")),
+    ("Sun Nov  6 08:49:37 1994", Some("Sun, 06 Nov 1994 08:49:37 GMT")),
+    ("Thu, 10 Apr 1980 16:33:12 GMT", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("10 Apr 1980 16:33:12", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("1980 Apr 10 16:33:12", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("16:33:12 10 Apr 1980", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("Apr 10 1980 16:33:12 GMT", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("Thu, 10 April 1980 16:33:12 GMT", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("THU, 10 APR 1980 16:33:12 GMT", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("Thu, 10th Apr 1980 16:33:12 GMT", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("Thu, 10 Apr 1980 16:33:12xyz GMT", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("Thu, 10 Apr 1980 16:33:12 +0100", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("Thu, 10 Apr 1980 16:33:12 GMT 1981", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("Thu, 10 Apr 1980 6:3:2 GMT", Some("Thu, 10 Apr 1980 06:03:02 GMT")),
+    ("  Thu, 10 Apr 1980 16:33:12 GMT  ", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("Thu, 10-Apr-80 16:33:12 GMT", Some("Thu, 10 Apr 1980 16:33:12 GMT")),
+    ("Thu, 10-Apr-70 16:33:12 GMT", Some("Fri, 10 Apr 1970 16:33:12 GMT")),
+    ("Thu, 10-Apr-69 16:33:12 GMT", Some("Wed, 10 Apr 2069 16:33:12 GMT")),
+    ("Thu, 10-Apr-00 16:33:12 GMT", Some("Mon, 10 Apr 2000 16:33:12 GMT")),
+    ("Thu, 10 Apr 0069 16:33:12 GMT", Some("Wed, 10 Apr 2069 16:33:12 GMT")),
+    ("Thu, 10 Apr 1601 16:33:12 GMT", Some("Tue, 10 Apr 1601 16:33:12 GMT")),
+    ("Thu, 10 Apr 9999 16:33:12 GMT", Some("Sat, 10 Apr 9999 16:33:12 GMT")),
+    ("Wed, 31 Dec 2008 23:59:59 GMT", Some("Wed, 31 Dec 2008 23:59:59 GMT")),
+    ("Tue, 29 Feb 2000 00:00:00 GMT", Some("Tue, 29 Feb 2000 00:00:00 GMT")),
+    ("Sat, 29 Feb 2020 12:00:00 GMT", Some("Sat, 29 Feb 2020 12:00:00 GMT")),
+    ("Thu, 31 Jan 1980 16:33:12 GMT", Some("Thu, 31 Jan 1980 16:33:12 GMT")),
+    ("", None),
+    ("GMT", None),
+    ("Thu, 10 Apr 1600 16:33:12 GMT", None),
+    ("Thu, 00 Apr 1980 16:33:12 GMT", None),
+    ("Thu, 32 Apr 1980 16:33:12 GMT", None),
+    ("Thu, 31 Apr 1980 16:33:12 GMT", None),
+    ("Thu, 29 Feb 1900 00:00:00 GMT", None),
+    ("Thu, 29 Feb 2001 00:00:00 GMT", None),
+    ("Thu, 10 Apr 1980 24:00:00 GMT", None),
+    ("Thu, 10 Apr 1980 16:60:12 GMT", None),
+    ("Thu, 10 Apr 1980 16:33:60 GMT", None),
+    ("Thu, 10 Apr 1980 GMT", None),
+    ("Thu, 10 1980 16:33:12 GMT", None),
+    ("Thu, Apr 1980 16:33:12 GMT", None),
+    ("Thu, 10 Apr 16:33:12 GMT", None),
+    ("Thu, 10 Apr 1 16:33:12 GMT", None),
+    ("Thu, 10 Apr 19800 16:33:12 GMT", None),
+    ("Thu, 10 Apr 1980 116:33:12 GMT", None),
+    ("Thu, 10 Apr 1980 16:33:123 GMT", None),
+    ("Thu, 10 Apr 1980 16:33 GMT", None),
+    ("Thu, 10 Apr 1980 16::12 GMT", None),
+    ("10Apr1980 16:33:12", None),
+    ("Thu, 100 Apr 1980 16:33:12 GMT", None),
+]
@@ -0,0 +1,251 @@
+//! Parser cases run against the jar.
+//!
+//! The cases in `tests/fixtures/http-state/parser` are those of the
+//! [http-state](https://github.com/abarth/http-state) test suite and the cases in
+//! `tests/fixtures/handwritten/parser` were written for this crate in the same format. Each test is
+//! a `NAME-test` file of `Set-Cookie` headers received from
+//! `http://home.example.org:8888/cookie-parser?NAME` and a `NAME-expected` file holding the
+//! `Cookie` header then sent to `http://home.example.org:8888/cookie-parser-result?NAME`, which is
+//! empty if no cookies are sent. A `Location` header in the test file sends the cookies to another
+//! URL instead, relative to the origin if it is a path.
+
+extern crate cookie_jar;
+extern crate url;
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use cookie_jar::{ClockFn, Cookie, Jar};
+use url::Url;
+
+/// Tests that are known not to pass yet.
+///
+/// Tests are removed from this list as the jar is brought into conformance with RFC6265.
+const KNOWN_FAILURES: &[&str] = &[
+    // Headers that do not follow the syntax of RFC6265 section 4.1 are rejected rather than
+    // parsed leniently as in section 5.2.
+    "0005",
+    "0007",
+    "0008",
+    "0009",
+    "0011",
+    "0019",
+    "charset0004",
+    "chromium0002",
+    "chromium0003",
+    "chromium0004",
+    "chromium0005",
+    "chromium0006",
+    "chromium0007",
+    "chromium0008",
+    "chromium0011",
+    "chromium0014",
+    "chromium0015",
+    "chromium0016",
+    "chromium0017",
+    "comma0002",
+    "comma0005",
+    "mozilla0012",
+    "name0005",
+    "name0012",
+    "name0013",
+    "name0018",
+    "name0019",
+    "name0020",
+    "name0021",
+    "name0027",
+    "name0030",
+    "path0026",
+    "path0027",
+    "path0029",
+    "value0003",
+    "value0004",
+    "value0006",
+    // Attributes must be separated by exactly "; ".
+    "local-0005",
+    // Whitespace around the name and value is not removed.
+    "local-0006",
+    "value0001",
+    // Attribute names are matched case sensitively, so lowercase attributes are ignored.
+    "attribute0002",
+    "domain0004",
+    "domain0005",
+    "domain0010",
+    "domain0011",
+    "domain0013",
+    "domain0014",
+    "domain0015",
+    "domain0016",
+    "domain0017",
+    "domain0018",
+    "domain0021",
+    "domain0025",
+    "domain0027",
+    "domain0031",
+    "domain0034",
+    "domain0037",
+    "mozilla0001",
+    "mozilla0002",
+    "mozilla0003",
+    "mozilla0005",
+    "mozilla0007",
+    "mozilla0009",
+    "mozilla0010",
+    "mozilla0013",
+    "optional-domain0042",
+    "ordering0001",
+    "path0001",
+    "path0003",
+    "path0005",
+    "path0006",
+    "path0008",
+    "path0009",
+    "path0011",
+    "path0012",
+    "path0013",
+    "path0014",
+    "path0018",
+    "path0024",
+    "path0025",
+    "path0028",
+    "path0031",
+    "path0032",
+    "local-attribute0001",
+    "local-attribute0002",
+    "local-attribute0003",
+    "local-attribute0006",
+    // Secure and Path attributes with unexpected values or whitespace are ignored.
+    "attribute0004",
+    "attribute0005",
+    "attribute0007",
+    "attribute0008",
+    "attribute0011",
+    "attribute0012",
+    "attribute0018",
+    // Invalid attribute values reject the whole cookie rather than being ignored.
+    "local-domain0007",
+    "local-expires0003",
+    "local-max-age0005",
+    "local-path0004",
+    // Domains that are public suffixes are accepted.
+    "local-domain0008",
+    // A path ending in "/" matches the request path without it.
+    "local-path0005",
+];
+
+/// The origin the cookies of each test are received from.
+const ORIGIN: &str = "http://home.example.org:8888";
+
+/// The number of times the clock has been read.
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+/// The time the tests are run at, between the dates the http-state cases expect to have passed
+/// and those they expect to be in the future.
+///
+/// The clock moves on by a nanosecond each time it is read so that cookies set one after another
+/// have distinct creation times.
+fn now() -> SystemTime {
+    let tick = TICKS.fetch_add(1, Ordering::SeqCst) as u64;
+    UNIX_EPOCH + Duration::from_secs(1_262_304_000) + Duration::from_nanos(tick)
+}
+
+/// The outcome of running a single test.
+struct Outcome {
+    name: String,
+    expected: String,
+    actual: String,
+}
+
+impl Outcome {
+    fn passed(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+/// Run the test with a given name.
+fn run(directory: &Path, name: &str) -> Outcome {
+    let read = |suffix: &str| {
+        let path = directory.join(format!("{}-{}", name, suffix));
+        fs::read_to_string(&path).expect("Could not read test")
+    };
+
+    let origin = Url::parse(&format!("{}/cookie-parser?{}", ORIGIN, name)).unwrap();
+    let mut result = format!("{}/cookie-parser-result?{}", ORIGIN, name);
+
+    let mut jar = Jar::with_clock(now as ClockFn);
+    for line in read("test").lines() {
+        let colon = match line.find(':') {
+            Some(colon) => colon,
+            None => continue,
+        };
+        let (header, value) = (&line[..colon], line[colon + 1..].trim());
+
+        if header.eq_ignore_ascii_case("Location") {
+            result = if value.starts_with('/') {
+                format!("{}{}", ORIGIN, value)
+            } else {
+                value.to_owned()
+            };
+        } else if header.eq_ignore_ascii_case("Set-Cookie") {
+            // A header that cannot be parsed is ignored.
+            if let Ok(cookie) = Cookie::parse(value, &origin) {
+                jar.add_cookie(cookie, None);
+            }
+        }
+    }
+    let result = Url::parse(&result).expect("Could not parse location");
+
+    let actual = match jar.cookie_header(&result, None) {
+        Some(header) => format!("Cookie: {}", header),
+        None => String::new(),
+    };
+
+    Outcome {
+        name: name.to_owned(),
+        expected: read("expected").trim().to_owned(),
+        actual,
+    }
+}
+
+#[test]
+fn parser() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut outcomes = Vec::new();
+    for directory in &["http-state/parser", "handwritten/parser"] {
+        let directory = fixtures.join(directory);
+        let mut names: Vec<_> = fs::read_dir(&directory)
+            .expect("Could not read tests")
+            .filter_map(|entry| {
+                let file_name = entry.unwrap().file_name().into_string().unwrap();
+                file_name.rfind("-test").map(|end| file_name[..end].to_owned())
+            })
+            .collect();
+        names.sort();
+        outcomes.extend(names.iter().map(|name| run(&directory, name)));
+    }
+
+    let mut unexpected = Vec::new();
+    for outcome in &outcomes {
+        let known = KNOWN_FAILURES.contains(&outcome.name.as_str());
+        if outcome.passed() && known {
+            unexpected.push(format!("{} now passes; remove it from KNOWN_FAILURES", outcome.name));
+        } else if !outcome.passed() && !known {
+            unexpected.push(format!(
+                "{} expected {:?} but got {:?}",
+                outcome.name, outcome.expected, outcome.actual
+            ));
+        }
+    }
+
+    for name in KNOWN_FAILURES {
+        assert!(
+            outcomes.iter().any(|outcome| outcome.name == *name),
+            "Unknown test {} in KNOWN_FAILURES",
+            name
+        );
+    }
+
+    assert!(unexpected.is_empty(), "\n{}", unexpected.join("\n"));
+}
@@ -0,0 +1,108 @@
+//! A shared jar used as the cookie store of a `reqwest` client talking to a local `hyper` server.
+
+extern crate cookie_jar;
+extern crate http_body_util;
+extern crate hyper;
+extern crate hyper_util;
+extern crate reqwest;
+extern crate tokio;
+extern crate url;
+
+use std::convert::Infallible;
+use std::future::{self, Ready};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::thread;
+
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{COOKIE, SET_COOKIE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+
+use cookie_jar::shared::SharedJar;
+use cookie_jar::{ClockFn, Jar};
+
+/// Set cookies on `/login` and `/logout` and echo the `Cookie` header of every request.
+fn handle(request: Request<Incoming>) -> Ready<Result<Response<Full<Bytes>>, Infallible>> {
+    let set_cookies: &[&str] = match request.uri().path() {
+        "/login" => &["session=31d4d96e407aad42; Path=/; HttpOnly", "theme=dark; Path=/app"],
+        "/logout" => &["session=deleted; Path=/; Max-Age=0"],
+        _ => &[],
+    };
+    let cookies = request
+        .headers()
+        .get(COOKIE)
+        .map(|cookies| Bytes::copy_from_slice(cookies.as_bytes()))
+        .unwrap_or_default();
+
+    let mut response = Response::builder();
+    for set_cookie in set_cookies {
+        response = response.header(SET_COOKIE, *set_cookie);
+    }
+    future::ready(Ok(response.body(Full::new(cookies)).unwrap()))
+}
+
+/// Serve connections on a local port for the rest of the test.
+///
+/// Connections are accepted on a thread of their own and served by a `tokio` runtime.
+fn serve() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            stream.set_nonblocking(true).unwrap();
+            let _context = runtime.enter();
+            let stream = tokio::net::TcpStream::from_std(stream).unwrap();
+            let connection = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service_fn(handle));
+            runtime.spawn(connection);
+        }
+    });
+
+    address
+}
+
+/// Get the `Cookie` header the server received for a request to a path.
+fn get(client: &reqwest::blocking::Client, address: SocketAddr, path: &str) -> String {
+    let url = format!("http://{}{}", address, path);
+    let response = client.get(&url).send().expect("Request failed");
+    response.text().expect("Could not read response")
+}
+
+#[test]
+fn client_cookies() {
+    let address = serve();
+    let jar: Arc<SharedJar<ClockFn>> = Arc::new(Jar::default().into());
+    let client = reqwest::blocking::Client::builder()
+        .cookie_provider(jar.clone())
+        .build()
+        .unwrap();
+
+    assert_eq!(get(&client, address, "/"), "");
+    assert_eq!(get(&client, address, "/login"), "");
+    assert_eq!(get(&client, address, "/"), "session=31d4d96e407aad42");
+    assert_eq!(
+        get(&client, address, "/app/settings"),
+        "theme=dark; session=31d4d96e407aad42"
+    );
+
+    // The cookies stored by the client can be read from the jar.
+    let url = format!("http://{}/app/", address).parse().unwrap();
+    let mut names: Vec<_> = {
+        let jar = jar.lock();
+        jar.url_matches(&url, None)
+            .map(|cookie| cookie.pair().name().to_owned())
+            .collect()
+    };
+    names.sort();
+    assert_eq!(names, vec!["session", "theme"]);
+
+    assert_eq!(get(&client, address, "/logout"), "session=31d4d96e407aad42");
+    assert_eq!(get(&client, address, "/app/"), "theme=dark");
+}